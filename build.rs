@@ -1,4 +1,31 @@
-const COMMANDS: &[&str] = &["load", "execute", "select", "close", "ping", "get_config"];
+const COMMANDS: &[&str] = &[
+    "load",
+    "execute",
+    "query",
+    "select",
+    "select_binary",
+    "select_stream",
+    "ack_stream",
+    "cancel_stream",
+    "close",
+    "ping",
+    "get_config",
+    "batch_params",
+    "execute_script",
+    "export_sql",
+    "export_query",
+    "import_file",
+    "backup",
+    "restore",
+    "import_sql",
+    "introspect",
+    "migrate_to",
+    "migration_plan",
+    "migration_status",
+    "begin_transaction",
+    "commit",
+    "rollback",
+];
 
 fn main() {
     tauri_plugin::Builder::new(COMMANDS)
@@ -42,6 +42,7 @@ pub fn run() {
     let config = tauri_plugin_libsql::Config {
         base_path: Some(cwd),
         encryption,
+        ..Default::default()
     };
 
     tauri::Builder::default()
@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core'
+import { encodeBindValues, reviveResult } from './values'
 
 /**
  * Callback function type for drizzle-orm/sqlite-proxy.
@@ -35,33 +36,32 @@ function createProxy(options: LoadOptions): SqliteProxyCallback {
       loaded = true
     }
 
-    const isSelect = /^\s*(\/\*[\s\S]*?\*\/\s*|--[^\n]*\n\s*)*SELECT\b/i.test(sql)
+    // Reads go through `select`, which can use the read pool and retries
+    // statements that write (`INSERT … RETURNING`) on the writer. `run` goes
+    // through `query`, which decides from the prepared statement whether it
+    // returns rows. Rows come back as arrays, the format Drizzle expects, and
+    // keep columns that share a name (e.g. `id` from both sides of a join).
+    const { rows } = reviveResult(
+      method === 'run'
+        ? await invoke<{ columns: string[]; rows: unknown[][] }>('plugin:libsql|query', {
+            db: options.path,
+            query: sql,
+            values: encodeBindValues(params),
+          })
+        : await invoke<{ columns: string[]; rows: unknown[][] }>('plugin:libsql|select', {
+            db: options.path,
+            query: sql,
+            values: encodeBindValues(params),
+            options: { format: 'columnar' },
+          }),
+    )
 
-    if (isSelect || method === 'all' || method === 'get' || method === 'values') {
-      const rows = await invoke<Record<string, unknown>[]>('plugin:libsql|select', {
-        db: options.path,
-        query: sql,
-        values: params as unknown[],
-      })
-
-      // Transform rows to the array-per-row format Drizzle expects.
-      // IndexMap on the Rust side guarantees column insertion order is preserved.
-      const transformedRows = rows.map((row) => Object.values(row))
-
-      if (method === 'get') {
-        return { rows: transformedRows[0] ? [transformedRows[0]] : [] }
-      }
-      // 'all' and 'values' both return all rows as arrays
-      return { rows: transformedRows }
+    if (method === 'get') {
+      return { rows: rows[0] ? [rows[0]] : [] }
     }
-
-    // INSERT / UPDATE / DELETE / DDL
-    await invoke('plugin:libsql|execute', {
-      db: options.path,
-      query: sql,
-      values: params as unknown[],
-    })
-    return { rows: [] }
+    // 'all' and 'values' return all rows as arrays; 'run' only needs them
+    // for `RETURNING`
+    return { rows }
   }
 }
 
@@ -1,4 +1,6 @@
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
+import { decode } from "./msgpack";
+import { encodeBindValues, reviveResult, type BindValues } from "./values";
 
 /** Cipher types for encryption */
 export type Cipher = "aes256cbc";
@@ -35,8 +37,61 @@ export interface LoadOptions {
    * Required when `syncUrl` is set or when `path` is a remote URL.
    */
   authToken?: string;
+  /**
+   * Number of read-only connections opened alongside the writer so `select`
+   * doesn't queue behind writes (local files only, at most 16). A pool
+   * switches the database file to WAL mode, which persists after closing.
+   * `0` disables the pool. Defaults to the plugin `Config` (0).
+   */
+  readPoolSize?: number;
+  /**
+   * How blobs appear in results, and whether number arrays in bind values
+   * are bound as blobs. Defaults to `"array"`. `Uint8Array`/`ArrayBuffer`
+   * bind values are bound as blobs in either mode.
+   */
+  blobEncoding?: BlobEncoding;
+  /**
+   * How integers beyond `Number.MAX_SAFE_INTEGER` are returned. Defaults to
+   * `"number"`, which rounds them.
+   */
+  integerEncoding?: IntegerEncoding;
+  /**
+   * What happens to NaN and ±Infinity, which JSON can't carry. Defaults to
+   * `"error"`.
+   */
+  nonFinite?: NonFinitePolicy;
 }
 
+/**
+ * What happens to NaN and ±Infinity in results and bind values.
+ * - `"error"`: the statement fails
+ * - `"null"`: they are read and bound as `null`
+ * - `"tagged"`: they are read and bound as-is
+ *
+ * `selectBinary()` always returns them as-is.
+ */
+export type NonFinitePolicy = "error" | "null" | "tagged";
+
+/**
+ * How integers outside JavaScript's safe range are returned by `select()`.
+ * - `"number"`: a plain number, rounded to the nearest double
+ * - `"string"`: a decimal string
+ * - `"bigint"`: a `bigint`
+ *
+ * `selectBinary()` always returns them as `bigint`.
+ */
+export type IntegerEncoding = "number" | "string" | "bigint";
+
+/**
+ * How blobs are represented in JSON results.
+ * - `"array"`: an array of byte values, e.g. `[0, 255]`
+ * - `"base64"`: a `Uint8Array`, sent over IPC as base64. It binds back as
+ *   a blob; plain strings in bind values still bind as text.
+ *
+ * `selectBinary()` always returns blobs as `Uint8Array`.
+ */
+export type BlobEncoding = "array" | "base64";
+
 /** Result of an execute operation */
 export interface QueryResult {
   /** Number of rows affected */
@@ -45,6 +100,502 @@ export interface QueryResult {
   lastInsertId: number;
 }
 
+/** A statement with bound parameters, for `batchParams()` */
+export interface BatchStatement {
+  query: string;
+  values?: BindValues;
+}
+
+/**
+ * Result of one `batchParams()` statement: the returned rows for statements
+ * that produce columns (SELECT, `RETURNING`, …), otherwise a `QueryResult`.
+ */
+export type BatchResult<T = Record<string, unknown>> = T[] | QueryResult;
+
+/** Options for `executeScript()` */
+export interface ScriptOptions {
+  /**
+   * Run the script in a transaction, so a failing statement undoes the
+   * whole script. Default: `false` — statements before the failing one
+   * stay applied.
+   */
+  atomic?: boolean;
+}
+
+/** Result of `executeScript()` */
+export interface ScriptResult {
+  /** Number of statements in the script */
+  statements: number;
+  /**
+   * Rows inserted, updated or deleted by the script's statements, counted
+   * like `execute()` does: rows changed by triggers aren't included
+   */
+  rowsAffected: number;
+}
+
+/**
+ * Where a migration stands: `"modified"` means it was applied, but its SQL
+ * has changed since.
+ */
+export type MigrationState = "applied" | "pending" | "modified";
+
+/** One entry of `migrationStatus()` */
+export interface MigrationStatus {
+  /** `null` for applied migrations that aren't configured in Rust */
+  version: number | null;
+  name: string;
+  state: MigrationState;
+  /** SHA-256 of the configured SQL */
+  checksum: string | null;
+  /** Checksum recorded when the migration was applied, if any */
+  appliedChecksum: string | null;
+  appliedAt: string | null;
+}
+
+/**
+ * What `Database.load()`, `restore()`, `migrateTo()` and `migrationPlan()`
+ * reject with when an applied Rust-configured migration has been edited
+ * since it ran. Other errors are plain message strings.
+ */
+export interface MigrationModifiedError {
+  kind: "migrationModified";
+  message: string;
+  /** Ledger name of the edited migration */
+  name: string;
+  /** Checksum recorded when the migration was applied */
+  expected: string;
+  /** Checksum of the migration's SQL now */
+  actual: string;
+}
+
+/** Whether `error`, as rejected by a plugin call, is a `MigrationModifiedError` */
+export function isMigrationModifiedError(
+  error: unknown
+): error is MigrationModifiedError {
+  return (
+    typeof error === "object" &&
+    error !== null &&
+    (error as { kind?: unknown }).kind === "migrationModified"
+  );
+}
+
+/** Result of `migrateTo()` */
+export interface MigrateToResult {
+  /** Migrations reverted, newest first */
+  reverted: string[];
+  /** Migrations applied, oldest first */
+  applied: string[];
+}
+
+/** A migration `migrationPlan()` would run */
+export interface PlannedMigration {
+  version: number;
+  name: string;
+  direction: "up" | "down";
+  /** Statements of its up or down script, in order */
+  statements: string[];
+}
+
+/** A table, index, view or trigger `migrationPlan()` would change */
+export interface SchemaChange {
+  kind: "created" | "modified" | "dropped";
+  objectType: "table" | "index" | "view" | "trigger";
+  name: string;
+  /** Definition afterwards; `null` when dropped */
+  sql: string | null;
+}
+
+/** Result of `migrationPlan()` */
+export interface MigrationPlan {
+  /** Reverts first, newest first, then applies, oldest first */
+  migrations: PlannedMigration[];
+  schemaChanges: SchemaChange[];
+}
+
+/** A schema (`main`, `temp` or an attached database), from `introspect()` */
+export interface SchemaInfo {
+  name: string;
+  /** Database file; `null` for in-memory and temporary databases */
+  file: string | null;
+  tables: TableInfo[];
+  views: ViewInfo[];
+  triggers: TriggerInfo[];
+}
+
+export interface TableInfo {
+  name: string;
+  /** The `CREATE TABLE` statement */
+  sql: string | null;
+  columns: ColumnInfo[];
+  indexes: IndexInfo[];
+  foreignKeys: ForeignKeyInfo[];
+}
+
+export interface ViewInfo {
+  name: string;
+  sql: string | null;
+  columns: ColumnInfo[];
+}
+
+export interface ColumnInfo {
+  name: string;
+  /** Declared type, e.g. `"INTEGER"` */
+  declType: string | null;
+  notNull: boolean;
+  /** Default value as SQL text, e.g. `"'untitled'"` */
+  defaultValue: string | null;
+  /** 1-based position in the primary key; `null` if not part of it */
+  primaryKey: number | null;
+  generated: boolean;
+}
+
+export interface IndexInfo {
+  name: string;
+  unique: boolean;
+  /** `"c"`: `CREATE INDEX`, `"u"`: UNIQUE constraint, `"pk"`: primary key */
+  origin: string;
+  /** Whether the index has a `WHERE` clause */
+  partial: boolean;
+  /** Indexed columns in order; `null` for expressions */
+  columns: (string | null)[];
+  /** The `CREATE INDEX` statement; `null` for indexes SQLite creates itself */
+  sql: string | null;
+}
+
+export interface ForeignKeyInfo {
+  /** Referenced table */
+  table: string;
+  /** Referencing columns */
+  columns: string[];
+  /** Referenced columns; `null` means the referenced table's primary key */
+  references: (string | null)[];
+  onUpdate: string;
+  onDelete: string;
+}
+
+export interface TriggerInfo {
+  name: string;
+  /** Table or view the trigger fires on */
+  table: string;
+  sql: string | null;
+}
+
+/** Result of `exportSql()` */
+export interface DumpResult {
+  /** Number of tables dumped */
+  tables: number;
+  /** Number of rows dumped, across all tables */
+  rows: number;
+}
+
+/** Options for `exportQuery()` */
+export interface ExportOptions {
+  /**
+   * - `"csv"` (default): RFC 4180 CSV with CRLF line endings
+   * - `"ndjson"`: one JSON object per line, keyed by column name
+   */
+  format?: "csv" | "ndjson";
+  /** Write the column names as the first CSV record. Default: `true` */
+  header?: boolean;
+  /** CSV field delimiter. Default: `","` */
+  delimiter?: string;
+  /**
+   * - `"necessary"` (default): quote only fields containing the delimiter,
+   *   a quote or a line break
+   * - `"all"`: quote every field
+   */
+  quote?: "necessary" | "all";
+  /** Encoding of blob values. Default: `"base64"` */
+  blobEncoding?: BlobEncoding;
+}
+
+/** Options for `backup()` */
+export interface BackupOptions {
+  /** Encrypt the backup with this key instead of the database's own */
+  encryption?: EncryptionConfig;
+  onProgress?: (progress: BackupProgress) => void;
+  /** Called once the copy is written, while its integrity is checked */
+  onVerifying?: () => void;
+}
+
+export interface BackupProgress {
+  bytesWritten: number;
+  /** Used size of the database, which the finished copy will have */
+  totalBytes: number;
+}
+
+/** Message pushed over a `backup` channel */
+type BackupEvent = { event: "progress"; data: BackupProgress } | { event: "verifying" };
+
+/** Options for `importFile()` */
+export interface ImportOptions {
+  /** `"csv"` (default) or `"ndjson"`, one JSON object per line */
+  format?: "csv" | "ndjson";
+  /** The first CSV record holds the field names. Default: `true` */
+  header?: boolean;
+  /** CSV field delimiter. Default: `","` */
+  delimiter?: string;
+  /**
+   * File field → table column. Only mapped fields are imported. Without
+   * it, every CSV header field, or every key of the first well-formed
+   * NDJSON object, goes to the column of the same name. Fields of a CSV
+   * file without a header are named by their one-based position: `"1"`,
+   * `"2"`, …
+   */
+  columns?: Record<string, string>;
+  /**
+   * Create the table, with untyped columns, if it doesn't exist. An empty
+   * file needs `columns` to name them.
+   */
+  createTable?: boolean;
+  /**
+   * Report records that fail to `onLineError` and carry on, rather than
+   * rolling back the whole import
+   */
+  skipErrors?: boolean;
+  /** Records between `onProgress` calls. Default: 1000 */
+  progressInterval?: number;
+  onProgress?: (progress: ImportProgress) => void;
+  onLineError?: (error: { line: number; message: string }) => void;
+}
+
+export interface ImportProgress {
+  rowsImported: number;
+  rowsFailed: number;
+  bytesRead: number;
+  totalBytes: number;
+}
+
+/** Result of `importFile()` */
+export interface ImportResult {
+  rowsImported: number;
+  /** Records skipped with `skipErrors` */
+  rowsFailed: number;
+}
+
+/** Message pushed over an `import_file` channel */
+type ImportEvent =
+  | { event: "progress"; data: ImportProgress }
+  | { event: "lineError"; data: { line: number; message: string } };
+
+/** Per-query options for `select()` */
+export interface SelectOptions {
+  /**
+   * Shape of the result.
+   * - `"objects"` (default): one object per row keyed by column name. Columns
+   *   that share a name (e.g. from a join) collapse into one key.
+   * - `"columnar"`: a `ColumnarResult`, with every row as an array in column
+   *   order and duplicate column names preserved.
+   *
+   * Only the columnar format reports each column's `metadata`.
+   */
+  format?: "objects" | "columnar";
+  /**
+   * Also convert values using each column's declared type: integers in
+   * `BOOLEAN`/`BOOL` columns become `true`/`false`. Other declared types
+   * (`DATETIME`, …) are returned as stored; read them from the columnar
+   * `metadata` to convert them yourself.
+   */
+  typed?: boolean;
+  /** Overrides the database's `blobEncoding` for this query */
+  blobEncoding?: BlobEncoding;
+  /** Overrides the database's `integerEncoding` for this query */
+  integerEncoding?: IntegerEncoding;
+  /**
+   * Columns whose integers are all returned per `integerEncoding`, not just
+   * those outside the safe range, so a column has one type in every row.
+   * Has no effect with `"number"`.
+   */
+  int64Columns?: string[];
+  /** Overrides the database's `nonFinite` policy for this query */
+  nonFinite?: NonFinitePolicy;
+}
+
+/**
+ * Declared type and origin of a result column. Only columnar results carry
+ * it: `select()` with `format: "columnar"`, `query()` and `selectBinary()`.
+ */
+export interface ColumnMetadata {
+  /** Name of the column in the result set */
+  name: string;
+  /**
+   * Type from the column's declaration, e.g. `"INTEGER"` or `"DATETIME"`.
+   * `null` for expressions and for remote databases.
+   */
+  declType: string | null;
+  /** Table the column is read from (`null` for expressions) */
+  table: string | null;
+  /** Name of the column in that table (`null` for expressions) */
+  column: string | null;
+  /** Schema the table lives in, e.g. `"main"` (`null` for expressions) */
+  database: string | null;
+}
+
+/** Result of `query()`: rows and write metadata together */
+export interface StatementResult<Row extends unknown[] = unknown[]>
+  extends ColumnarResult<Row>,
+    QueryResult {}
+
+/** Result of `select()` with `format: "columnar"` */
+export interface ColumnarResult<Row extends unknown[] = unknown[]> {
+  /** Column names in order, duplicates included */
+  columns: string[];
+  /** Declared type and origin of each column, in the same order */
+  metadata: ColumnMetadata[];
+  rows: Row[];
+}
+
+/** Options for `selectStream()` */
+export interface SelectStreamOptions {
+  /** Rows per chunk. Defaults to 256. */
+  chunkSize?: number;
+  /** Cancels the stream and drops the cursor on the Rust side. */
+  signal?: AbortSignal;
+}
+
+/** Message pushed over a `select_stream` channel */
+type StreamEvent<T> =
+  | { event: "rows"; data: T[] }
+  | { event: "done"; data: { totalRows: number } }
+  | { event: "error"; data: string };
+
+/** Options for beginning an interactive transaction */
+export interface TransactionOptions {
+  /**
+   * Roll the transaction back automatically if it is neither committed nor
+   * rolled back within this many milliseconds.
+   * Defaults to the plugin's `transaction_timeout` (30 seconds).
+   */
+  timeoutMs?: number;
+}
+
+/**
+ * **Transaction**
+ *
+ * An interactive transaction held open on the Rust side. Statements run
+ * through it see each other's uncommitted writes; statements run directly on
+ * the `Database` wait until the transaction is committed or rolled back.
+ */
+export class Transaction {
+  /** The database path */
+  db: string;
+  /** Server-side transaction id */
+  id: number;
+
+  constructor(db: string, id: number) {
+    this.db = db;
+    this.id = id;
+  }
+
+  /** Same as `Database.execute`, but inside this transaction. */
+  async execute(query: string, bindValues?: BindValues): Promise<QueryResult> {
+    return invoke<QueryResult>("plugin:libsql|execute", {
+      db: this.db,
+      query,
+      values: encodeBindValues(bindValues),
+      transactionId: this.id,
+    });
+  }
+
+  /** Same as `Database.query`, but inside this transaction. */
+  async query<Row extends unknown[] = unknown[]>(
+    query: string,
+    bindValues?: BindValues,
+    options?: SelectOptions
+  ): Promise<StatementResult<Row>> {
+    const result = await invoke<StatementResult<Row>>("plugin:libsql|query", {
+      db: this.db,
+      query,
+      values: encodeBindValues(bindValues),
+      transactionId: this.id,
+      options,
+    });
+    return reviveResult(result);
+  }
+
+  /** Same as `Database.select`, but inside this transaction. */
+  async select<T>(
+    query: string,
+    bindValues?: BindValues,
+    options?: SelectOptions
+  ): Promise<T> {
+    const result = await invoke<T>("plugin:libsql|select", {
+      db: this.db,
+      query,
+      values: encodeBindValues(bindValues),
+      transactionId: this.id,
+      options,
+    });
+    return reviveResult(result);
+  }
+
+  /** Same as `Database.selectBinary`, but inside this transaction. */
+  async selectBinary<Row extends unknown[] = unknown[]>(
+    query: string,
+    bindValues?: BindValues,
+    options?: Pick<SelectOptions, "typed">
+  ): Promise<ColumnarResult<Row>> {
+    const bytes = await invoke<ArrayBuffer>("plugin:libsql|select_binary", {
+      db: this.db,
+      query,
+      values: encodeBindValues(bindValues),
+      transactionId: this.id,
+      options,
+    });
+    return decode(bytes) as ColumnarResult<Row>;
+  }
+
+  /**
+   * Same as `Database.batchParams`, but inside this transaction. The batch
+   * runs in a savepoint, so a failure only undoes the batch's own statements.
+   */
+  async batchParams(statements: BatchStatement[]): Promise<BatchResult[]> {
+    const results = await invoke<BatchResult[]>("plugin:libsql|batch_params", {
+      db: this.db,
+      statements: statements.map((s) => ({ query: s.query, values: encodeBindValues(s.values) })),
+      transactionId: this.id,
+    });
+    return reviveResult(results);
+  }
+
+  /**
+   * Same as `Database.executeScript`, but inside this transaction. With
+   * `atomic`, the script runs in a savepoint.
+   */
+  async executeScript(script: string, options?: ScriptOptions): Promise<ScriptResult> {
+    return await invoke<ScriptResult>("plugin:libsql|execute_script", {
+      db: this.db,
+      script,
+      transactionId: this.id,
+      atomic: options?.atomic,
+    });
+  }
+
+  /** Same as `Database.introspect`, but sees this transaction's changes. */
+  async introspect(): Promise<SchemaInfo[]> {
+    return await invoke<SchemaInfo[]>("plugin:libsql|introspect", {
+      db: this.db,
+      transactionId: this.id,
+    });
+  }
+
+  /** Commits the transaction. */
+  async commit(): Promise<void> {
+    await invoke("plugin:libsql|commit", {
+      db: this.db,
+      transactionId: this.id,
+    });
+  }
+
+  /** Rolls the transaction back. */
+  async rollback(): Promise<void> {
+    await invoke("plugin:libsql|rollback", {
+      db: this.db,
+      transactionId: this.id,
+    });
+  }
+}
+
 /**
  * **Database**
  *
@@ -111,17 +662,52 @@ export class Database {
    *   "UPDATE todos SET title = $1, completed = $2 WHERE id = $3",
    *   [todos.title, todos.status, todos.id]
    * );
+   * // Named parameters
+   * const result = await db.execute(
+   *   "UPDATE todos SET title = :title WHERE id = :id",
+   *   { id: todos.id, title: todos.title }
+   * );
    * ```
    */
-  async execute(query: string, bindValues?: unknown[]): Promise<QueryResult> {
+  async execute(query: string, bindValues?: BindValues): Promise<QueryResult> {
     const result = await invoke<QueryResult>("plugin:libsql|execute", {
       db: this.path,
       query,
-      values: bindValues ?? [],
+      values: encodeBindValues(bindValues),
     });
     return result;
   }
 
+  /**
+   * **query**
+   *
+   * Runs any statement and returns its rows (as with `format: "columnar"`)
+   * together with `rowsAffected` and `lastInsertId`. Use it when you don't
+   * know up front whether a statement returns rows — `WITH … SELECT`,
+   * `INSERT … RETURNING`, `PRAGMA` — or need both. Always runs on the
+   * writer connection.
+   *
+   * @example
+   * ```ts
+   * const { rows, rowsAffected } = await db.query<[number]>(
+   *   "DELETE FROM todos WHERE completed = 1 RETURNING id"
+   * );
+   * ```
+   */
+  async query<Row extends unknown[] = unknown[]>(
+    query: string,
+    bindValues?: BindValues,
+    options?: SelectOptions
+  ): Promise<StatementResult<Row>> {
+    const result = await invoke<StatementResult<Row>>("plugin:libsql|query", {
+      db: this.path,
+      query,
+      values: encodeBindValues(bindValues),
+      options,
+    });
+    return reviveResult(result);
+  }
+
   /**
    * **select**
    *
@@ -133,15 +719,153 @@ export class Database {
    *   "SELECT * FROM todos WHERE id = $1",
    *   [id]
    * );
+   *
+   * // Columnar: column names once, rows as arrays, duplicate names kept
+   * const { columns, rows } = await db.select<ColumnarResult>(
+   *   "SELECT a.id, b.id FROM a JOIN b ON b.a_id = a.id",
+   *   [],
+   *   { format: "columnar" }
+   * );
    * ```
    */
-  async select<T>(query: string, bindValues?: unknown[]): Promise<T> {
+  async select<T>(
+    query: string,
+    bindValues?: BindValues,
+    options?: SelectOptions
+  ): Promise<T> {
     const result = await invoke<T>("plugin:libsql|select", {
       db: this.path,
       query,
-      values: bindValues ?? [],
+      values: encodeBindValues(bindValues),
+      options,
     });
-    return result;
+    return reviveResult(result);
+  }
+
+  /**
+   * **selectBinary**
+   *
+   * Same as `select` with `format: "columnar"`, but the result crosses IPC as
+   * MessagePack instead of JSON, which is much cheaper for large result sets.
+   * Blobs arrive as `Uint8Array`, and integers beyond
+   * `Number.MAX_SAFE_INTEGER` as `bigint`.
+   *
+   * @example
+   * ```ts
+   * const { columns, rows } = await db.selectBinary<[number, Uint8Array]>(
+   *   "SELECT id, thumbnail FROM photos"
+   * );
+   * ```
+   */
+  async selectBinary<Row extends unknown[] = unknown[]>(
+    query: string,
+    bindValues?: BindValues,
+    options?: Pick<SelectOptions, "typed">
+  ): Promise<ColumnarResult<Row>> {
+    const bytes = await invoke<ArrayBuffer>("plugin:libsql|select_binary", {
+      db: this.path,
+      query,
+      values: encodeBindValues(bindValues),
+      options,
+    });
+    return decode(bytes) as ColumnarResult<Row>;
+  }
+
+  /**
+   * **selectStream**
+   *
+   * Runs a query and delivers its rows in chunks as the Rust side reads them,
+   * instead of materializing the whole result. Chunks are handed to `onRows`
+   * one at a time; the stream pauses while `onRows` is busy, so a slow
+   * consumer never buffers more than a couple of chunks.
+   *
+   * Resolves with the total number of rows once the cursor is exhausted.
+   * Aborting `options.signal` (or throwing from `onRows`) cancels the stream
+   * and rejects. It also rejects if the stream is cancelled elsewhere (e.g.
+   * by `close`) or `onRows` takes longer than 30 seconds.
+   *
+   * `onRows` may write to the same database. Without a read pool
+   * (`readPoolSize`), though, the cursor shares the writer connection until
+   * the stream ends: `beginTransaction()` rejects, and DDL on a table being
+   * streamed fails with `database table is locked`.
+   *
+   * @example
+   * ```ts
+   * const controller = new AbortController();
+   * const total = await db.selectStream<{ id: number }>(
+   *   "SELECT * FROM events ORDER BY id",
+   *   [],
+   *   async (rows) => { await appendToTable(rows); },
+   *   { chunkSize: 1000, signal: controller.signal }
+   * );
+   * ```
+   */
+  async selectStream<T>(
+    query: string,
+    bindValues: BindValues | undefined,
+    onRows: (rows: T[]) => void | Promise<void>,
+    options: SelectStreamOptions = {}
+  ): Promise<number> {
+    options.signal?.throwIfAborted();
+
+    let stopped = false;
+    let queue = Promise.resolve();
+    const channel = new Channel<StreamEvent<T>>();
+    const streamId = invoke<number>("plugin:libsql|select_stream", {
+      db: this.path,
+      query,
+      values: encodeBindValues(bindValues),
+      chunkSize: options.chunkSize,
+      onEvent: channel,
+    });
+
+    const finished = new Promise<number>((resolve, reject) => {
+      const stop = (error: unknown) => {
+        if (stopped) return;
+        stopped = true;
+        reject(error);
+        streamId
+          .then((streamId) =>
+            invoke("plugin:libsql|cancel_stream", { db: this.path, streamId })
+          )
+          .catch(() => {});
+      };
+
+      options.signal?.addEventListener("abort", () => stop(options.signal?.reason), {
+        once: true,
+      });
+
+      // Handle messages strictly in order, one at a time
+      channel.onmessage = (message) => {
+        queue = queue.then(async () => {
+          if (stopped) return;
+          try {
+            switch (message.event) {
+              case "rows":
+                await onRows(reviveResult(message.data));
+                await invoke("plugin:libsql|ack_stream", {
+                  db: this.path,
+                  streamId: await streamId,
+                });
+                break;
+              case "done":
+                stopped = true;
+                resolve(message.data.totalRows);
+                break;
+              case "error":
+                stopped = true;
+                reject(new Error(message.data));
+                break;
+            }
+          } catch (e) {
+            stop(e);
+          }
+        });
+      };
+    });
+
+    await streamId;
+    return finished;
   }
 
   /**
@@ -179,6 +903,115 @@ export class Database {
     await invoke("plugin:libsql|batch", { db: this.path, queries });
   }
 
+  /**
+   * **batchParams**
+   *
+   * Like `batch()`, but every statement can use bound parameters, and the
+   * result of each statement is returned in order. Runs in a single
+   * transaction — if any statement fails the whole batch is rolled back.
+   *
+   * @example
+   * ```ts
+   * const [insert, rows] = await db.batchParams([
+   *   { query: "INSERT INTO todos (title) VALUES ($1)", values: ["Buy milk"] },
+   *   { query: "SELECT count(*) AS n FROM todos" },
+   * ]);
+   * ```
+   */
+  async batchParams(statements: BatchStatement[]): Promise<BatchResult[]> {
+    const results = await invoke<BatchResult[]>("plugin:libsql|batch_params", {
+      db: this.path,
+      statements: statements.map((s) => ({ query: s.query, values: encodeBindValues(s.values) })),
+    });
+    return reviveResult(results);
+  }
+
+  /**
+   * **executeScript**
+   *
+   * Runs a whole SQL script, such as a migration or seed file, statement by
+   * statement. Statements are split the way SQLite does, so `;` inside
+   * string literals, comments and `CREATE TRIGGER` bodies is fine. If a
+   * statement fails, the error names its index, line and byte offset.
+   *
+   * @example
+   * ```ts
+   * const sql = await fetch("/schema.sql").then((r) => r.text());
+   * await db.executeScript(sql, { atomic: true });
+   * ```
+   */
+  async executeScript(script: string, options?: ScriptOptions): Promise<ScriptResult> {
+    return await invoke<ScriptResult>("plugin:libsql|execute_script", {
+      db: this.path,
+      script,
+      atomic: options?.atomic,
+    });
+  }
+
+  /**
+   * **beginTransaction**
+   *
+   * Begins an interactive transaction. Unlike `batch()`, statements can use
+   * bound parameters and you can read results before deciding what to write.
+   * Always finish with `commit()` or `rollback()` — the transaction holds the
+   * database write lock until then (or until `timeoutMs` elapses).
+   *
+   * @example
+   * ```ts
+   * const tx = await db.beginTransaction();
+   * try {
+   *   const [{ balance }] = await tx.select<{ balance: number }[]>(
+   *     "SELECT balance FROM accounts WHERE id = $1",
+   *     [from]
+   *   );
+   *   if (balance < amount) throw new Error("insufficient funds");
+   *   await tx.execute("UPDATE accounts SET balance = balance - $1 WHERE id = $2", [amount, from]);
+   *   await tx.execute("UPDATE accounts SET balance = balance + $1 WHERE id = $2", [amount, to]);
+   *   await tx.commit();
+   * } catch (e) {
+   *   await tx.rollback();
+   *   throw e;
+   * }
+   * ```
+   */
+  async beginTransaction(options?: TransactionOptions): Promise<Transaction> {
+    const id = await invoke<number>("plugin:libsql|begin_transaction", {
+      db: this.path,
+      timeoutMs: options?.timeoutMs,
+    });
+    return new Transaction(this.path, id);
+  }
+
+  /**
+   * **transaction**
+   *
+   * Runs `fn` inside an interactive transaction, committing when it resolves
+   * and rolling back when it throws.
+   *
+   * @example
+   * ```ts
+   * const id = await db.transaction(async (tx) => {
+   *   const { lastInsertId } = await tx.execute("INSERT INTO users (name) VALUES ($1)", [name]);
+   *   await tx.execute("INSERT INTO profiles (user_id) VALUES ($1)", [lastInsertId]);
+   *   return lastInsertId;
+   * });
+   * ```
+   */
+  async transaction<T>(
+    fn: (tx: Transaction) => Promise<T>,
+    options?: TransactionOptions
+  ): Promise<T> {
+    const tx = await this.beginTransaction(options);
+    try {
+      const result = await fn(tx);
+      await tx.commit();
+      return result;
+    } catch (e) {
+      await tx.rollback().catch(() => {});
+      throw e;
+    }
+  }
+
   /**
    * **sync**
    *
@@ -204,6 +1037,274 @@ export class Database {
     await invoke("plugin:libsql|sync", { db: this.path });
   }
 
+  /**
+   * **exportSql**
+   *
+   * Writes a plain-SQL dump of the database — `CREATE` statements and
+   * `INSERT`s, tables in foreign-key dependency order — to `path`, relative
+   * to the plugin base path. The dump is a consistent snapshot, and doesn't
+   * block writes when the database has a read pool. An existing file at
+   * `path` is only replaced once the dump is complete, and a loaded
+   * database's files are rejected as targets. Encrypted databases are read
+   * with their existing key; the dump itself is plain text.
+   *
+   * @example
+   * ```ts
+   * const { rows } = await db.exportSql("support/dump.sql");
+   * ```
+   */
+  async exportSql(path: string): Promise<DumpResult> {
+    return await invoke<DumpResult>("plugin:libsql|export_sql", { db: this.path, path });
+  }
+
+  /**
+   * **exportQuery**
+   *
+   * Runs a query and writes its rows to `path`, relative to the plugin base
+   * path, as CSV or NDJSON. Rows are written as they are read, so large
+   * results never pass through memory or the IPC bridge. Integers are
+   * written exactly, whatever their size. An existing file at `path` is
+   * only replaced once the export succeeds, and a loaded database's files
+   * are rejected as targets. Returns the number of rows written.
+   *
+   * @example
+   * ```ts
+   * const count = await db.exportQuery(
+   *   "SELECT * FROM orders WHERE created_at >= $1",
+   *   ["2024-01-01"],
+   *   "exports/orders.csv",
+   * );
+   * ```
+   */
+  async exportQuery(
+    query: string,
+    bindValues: BindValues | undefined,
+    path: string,
+    options?: ExportOptions,
+  ): Promise<number> {
+    return await invoke<number>("plugin:libsql|export_query", {
+      db: this.path,
+      query,
+      values: encodeBindValues(bindValues),
+      path,
+      options,
+    });
+  }
+
+  /**
+   * **backup**
+   *
+   * Writes a consistent, compacted copy of the database to `path`, relative
+   * to the plugin base path, with `VACUUM INTO` — safe while the database is
+   * in use. With a read pool (`readPoolSize`) writers aren't blocked;
+   * without one, writes wait until the copy is done. The copy keeps the
+   * database's encryption key, or uses `options.encryption`. It passes an
+   * integrity check before replacing any existing file at `path`; a loaded
+   * database's files are rejected as targets. Resolves to its size in
+   * bytes. Local databases only.
+   *
+   * @example
+   * ```ts
+   * await db.backup("backups/app.db", {
+   *   onProgress: ({ bytesWritten, totalBytes }) => setProgress(bytesWritten / totalBytes),
+   * });
+   * ```
+   */
+  async backup(path: string, options: BackupOptions = {}): Promise<number> {
+    const { encryption, onProgress, onVerifying } = options;
+    const channel = new Channel<BackupEvent>();
+    channel.onmessage = (message) => {
+      switch (message.event) {
+        case "progress":
+          onProgress?.(message.data);
+          break;
+        case "verifying":
+          onVerifying?.();
+          break;
+      }
+    };
+    return await invoke<number>("plugin:libsql|backup", {
+      db: this.path,
+      path,
+      encryption: encryption && { ...encryption, key: Array.from(encryption.key) },
+      onEvent: channel,
+    });
+  }
+
+  /**
+   * **restore**
+   *
+   * Replaces the database with the backup at `path`, relative to the plugin
+   * base path, and reopens it. The backup is integrity-checked with its key
+   * first — the database's own, or `encryption` if the backup uses a
+   * different one; it's re-encrypted with the database's key. New statements
+   * then wait while in-flight ones finish; if any still run after 10
+   * seconds, it rejects without touching anything. Otherwise open
+   * transactions are rolled back and streams cancelled, then the stale
+   * `-wal`/`-shm` files are removed and the file is renamed into place. This
+   * isn't crash-atomic: a crash between the two can leave the old database
+   * without its `-wal`. Every `Database` for this path, in any window, keeps
+   * working and sees the restored data. Local databases only.
+   *
+   * @example
+   * ```ts
+   * await db.restore("backups/app.db");
+   * ```
+   */
+  async restore(path: string, options: { encryption?: EncryptionConfig } = {}): Promise<void> {
+    const { encryption } = options;
+    await invoke("plugin:libsql|restore", {
+      db: this.path,
+      path,
+      encryption: encryption && { ...encryption, key: Array.from(encryption.key) },
+    });
+  }
+
+  /**
+   * **importFile**
+   *
+   * Imports a CSV or NDJSON file at `path`, relative to the plugin base
+   * path, into `table` — in one transaction, with a single prepared
+   * `INSERT`, entirely on the Rust side. If a record fails, the import is
+   * rolled back and the error names its line, unless `skipErrors` is set.
+   *
+   * @example
+   * ```ts
+   * const { rowsImported } = await db.importFile("imports/contacts.csv", "contacts", {
+   *   columns: { "E-mail": "email", Name: "name" },
+   *   onProgress: ({ bytesRead, totalBytes }) => setProgress(bytesRead / totalBytes),
+   * });
+   * ```
+   */
+  async importFile(path: string, table: string, options: ImportOptions = {}): Promise<ImportResult> {
+    const { onProgress, onLineError, ...rest } = options;
+    const channel = new Channel<ImportEvent>();
+    channel.onmessage = (message) => {
+      switch (message.event) {
+        case "progress":
+          onProgress?.(message.data);
+          break;
+        case "lineError":
+          onLineError?.(message.data);
+          break;
+      }
+    };
+    return await invoke<ImportResult>("plugin:libsql|import_file", {
+      db: this.path,
+      path,
+      table,
+      options: rest,
+      onEvent: channel,
+    });
+  }
+
+  /**
+   * **importSql**
+   *
+   * Restores a dump from `path`, relative to the plugin base path, into
+   * this database, which must be empty. A dump from `exportSql()` restores
+   * atomically: if a statement fails, nothing is kept and the error names
+   * the statement and its line.
+   *
+   * @example
+   * ```ts
+   * const db = await Database.load("sqlite:restored.db");
+   * await db.importSql("support/dump.sql");
+   * ```
+   */
+  async importSql(path: string): Promise<ScriptResult> {
+    return await invoke<ScriptResult>("plugin:libsql|import_sql", { db: this.path, path });
+  }
+
+  /**
+   * **introspect**
+   *
+   * Describes the database's schema: every table with its columns,
+   * indexes and foreign keys, views and triggers — for `main` and every
+   * attached database (and `temp`, once used).
+   *
+   * @example
+   * ```ts
+   * const [main] = await db.introspect();
+   * for (const table of main.tables) {
+   *   console.log(table.name, table.columns.map((c) => c.name));
+   * }
+   * ```
+   */
+  async introspect(): Promise<SchemaInfo[]> {
+    return await invoke<SchemaInfo[]>("plugin:libsql|introspect", { db: this.path });
+  }
+
+  /**
+   * **migrationStatus**
+   *
+   * Lists the migrations configured for this database in Rust
+   * (`Config::migrations`) as applied, pending or modified, followed by
+   * any other applied migrations found in its ledger.
+   *
+   * @example
+   * ```ts
+   * const modified = (await db.migrationStatus()).filter((m) => m.state === "modified");
+   * ```
+   */
+  async migrationStatus(): Promise<MigrationStatus[]> {
+    return await invoke<MigrationStatus[]>("plugin:libsql|migration_status", {
+      db: this.path,
+    });
+  }
+
+  /**
+   * **migrationPlan**
+   *
+   * Dry run of `migrateTo(version)`, or of what `load` would apply when
+   * `version` is omitted: every pending Rust-configured migration, up to the
+   * version a previous `migrateTo` moved the database back to. The migrations run
+   * for real inside a savepoint that is then rolled back, and the plan lists
+   * the statements executed and the resulting schema changes. A migration
+   * that would fail rejects here.
+   *
+   * @example
+   * ```ts
+   * const plan = await db.migrationPlan();
+   * for (const change of plan.schemaChanges) {
+   *   console.log(change.kind, change.objectType, change.name);
+   * }
+   * ```
+   */
+  async migrationPlan(version?: number): Promise<MigrationPlan> {
+    return await invoke<MigrationPlan>("plugin:libsql|migration_plan", {
+      db: this.path,
+      version,
+    });
+  }
+
+  /**
+   * **migrateTo**
+   *
+   * Moves the database to the given version of its Rust-configured
+   * migrations: applied migrations above it are reverted with their down
+   * scripts, newest first, in a single transaction; pending ones up to it
+   * are applied. Fails without changing anything if a migration to revert
+   * has no down script.
+   *
+   * A version below the newest migration is remembered in the database:
+   * `Database.load()` and `restore()` then apply pending migrations only up
+   * to it, so the rollback survives a restart. Migrate to the newest version
+   * to resume applying migrations on load.
+   *
+   * @example
+   * ```ts
+   * // Undo the last release's migration
+   * await db.migrateTo(4);
+   * ```
+   */
+  async migrateTo(version: number): Promise<MigrateToResult> {
+    return await invoke<MigrateToResult>("plugin:libsql|migrate_to", {
+      db: this.path,
+      version,
+    });
+  }
+
   async close(db?: string): Promise<boolean> {
     const success = await invoke<boolean>("plugin:libsql|close", { db });
     return success;
@@ -228,6 +1329,9 @@ export async function getConfig(): Promise<ConfigInfo> {
 // Re-export for drizzle integration
 export { createDrizzleProxy } from "./drizzle";
 
+export { blob, int64, real } from "./values";
+export type { BindValues, TypedValue } from "./values";
+
 // Re-export migration utility
 export { migrate } from "./migrate";
 export type { MigrationFiles, MigrateOptions } from "./migrate";
@@ -52,7 +52,8 @@ function parseMigrationPath(path: string): Omit<ParsedMigration, 'sql'> | null {
   }
 
   const match = fileName.match(/^(\d+)[_\-].*\.sql$/)
-  if (!match) {
+  // Down scripts are only used by the Rust migration engine
+  if (!match || fileName.endsWith('.down.sql')) {
     return null
   }
 
@@ -151,22 +152,15 @@ export async function migrate(
       continue
     }
 
-    // Split on semicolons to get individual statements.
-    // Note: this is a naive split — semicolons inside string literals will
-    // cause incorrect splits. drizzle-kit generated SQL does not produce this.
-    const statements = migration.sql
-      .split(';')
-      .map((s) => s.trim())
-      .filter((s) => s.length > 0)
-
     // Record the migration in the same transaction as the schema changes so
-    // a partial failure leaves no trace. One invoke for the entire migration.
+    // a partial failure leaves no trace. The script is split the way SQLite
+    // would, so `;` inside strings, comments and trigger bodies is safe; the
+    // newline ends a trailing line comment.
     const safeName = migration.filename.replace(/'/g, "''")
-    statements.push(`INSERT INTO ${table} (hash) VALUES ('${safeName}')`)
-
-    await invoke('plugin:libsql|batch', {
+    await invoke('plugin:libsql|execute_script', {
       db: dbPath,
-      queries: statements,
+      script: `${migration.sql}\n;\nINSERT INTO ${table} (hash) VALUES ('${safeName}');`,
+      atomic: true,
     })
   }
 }
@@ -0,0 +1,155 @@
+/**
+ * Minimal MessagePack decoder for `select_binary` responses.
+ *
+ * Covers the formats the Rust encoder (`src/msgpack.rs`) emits plus float32,
+ * so results decode without pulling in a MessagePack dependency.
+ */
+
+const textDecoder = new TextDecoder();
+
+/**
+ * Decodes one MessagePack value.
+ *
+ * - `bin` becomes a `Uint8Array`
+ * - 64-bit integers outside `Number.MAX_SAFE_INTEGER` become `bigint`
+ * - maps become plain objects
+ */
+export function decode(input: ArrayBuffer | Uint8Array): unknown {
+  const bytes = input instanceof Uint8Array ? input : new Uint8Array(input);
+  const reader = new Reader(bytes);
+  const value = reader.value();
+  if (reader.offset !== bytes.length) {
+    throw new Error(
+      `msgpack: ${bytes.length - reader.offset} trailing bytes after value`
+    );
+  }
+  return value;
+}
+
+class Reader {
+  offset = 0;
+  private view: DataView;
+
+  constructor(private bytes: Uint8Array) {
+    this.view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+  }
+
+  value(): unknown {
+    const tag = this.u8();
+
+    if (tag <= 0x7f) return tag;
+    if (tag >= 0xe0) return tag - 0x100;
+    if ((tag & 0xf0) === 0x80) return this.map(tag & 0x0f);
+    if ((tag & 0xf0) === 0x90) return this.array(tag & 0x0f);
+    if ((tag & 0xe0) === 0xa0) return this.str(tag & 0x1f);
+
+    switch (tag) {
+      case 0xc0:
+        return null;
+      case 0xc2:
+        return false;
+      case 0xc3:
+        return true;
+      case 0xc4:
+        return this.bin(this.u8());
+      case 0xc5:
+        return this.bin(this.u16());
+      case 0xc6:
+        return this.bin(this.u32());
+      case 0xca:
+        return this.read(4, (o) => this.view.getFloat32(o));
+      case 0xcb:
+        return this.read(8, (o) => this.view.getFloat64(o));
+      case 0xcc:
+        return this.u8();
+      case 0xcd:
+        return this.u16();
+      case 0xce:
+        return this.u32();
+      case 0xcf:
+        return toNumber(this.read(8, (o) => this.view.getBigUint64(o)));
+      case 0xd0:
+        return this.read(1, (o) => this.view.getInt8(o));
+      case 0xd1:
+        return this.read(2, (o) => this.view.getInt16(o));
+      case 0xd2:
+        return this.read(4, (o) => this.view.getInt32(o));
+      case 0xd3:
+        return toNumber(this.read(8, (o) => this.view.getBigInt64(o)));
+      case 0xd9:
+        return this.str(this.u8());
+      case 0xda:
+        return this.str(this.u16());
+      case 0xdb:
+        return this.str(this.u32());
+      case 0xdc:
+        return this.array(this.u16());
+      case 0xdd:
+        return this.array(this.u32());
+      case 0xde:
+        return this.map(this.u16());
+      case 0xdf:
+        return this.map(this.u32());
+      default:
+        throw new Error(
+          `msgpack: unsupported type 0x${tag.toString(16)} at offset ${this.offset - 1}`
+        );
+    }
+  }
+
+  private array(len: number): unknown[] {
+    const out = new Array(len);
+    for (let i = 0; i < len; i++) out[i] = this.value();
+    return out;
+  }
+
+  private map(len: number): Record<string, unknown> {
+    const out: Record<string, unknown> = {};
+    for (let i = 0; i < len; i++) {
+      const key = this.value();
+      out[String(key)] = this.value();
+    }
+    return out;
+  }
+
+  private str(len: number): string {
+    return textDecoder.decode(this.take(len));
+  }
+
+  private bin(len: number): Uint8Array {
+    // Copy, so the result doesn't keep the whole response buffer alive
+    return this.take(len).slice();
+  }
+
+  private u8(): number {
+    return this.read(1, (o) => this.view.getUint8(o));
+  }
+
+  private u16(): number {
+    return this.read(2, (o) => this.view.getUint16(o));
+  }
+
+  private u32(): number {
+    return this.read(4, (o) => this.view.getUint32(o));
+  }
+
+  private take(len: number): Uint8Array {
+    return this.read(len, (o) => this.bytes.subarray(o, o + len));
+  }
+
+  private read<T>(len: number, get: (offset: number) => T): T {
+    if (this.offset + len > this.bytes.length) {
+      throw new Error("msgpack: unexpected end of input");
+    }
+    const value = get(this.offset);
+    this.offset += len;
+    return value;
+  }
+}
+
+function toNumber(value: bigint): number | bigint {
+  return value >= BigInt(Number.MIN_SAFE_INTEGER) &&
+    value <= BigInt(Number.MAX_SAFE_INTEGER)
+    ? Number(value)
+    : value;
+}
@@ -0,0 +1,131 @@
+/**
+ * Conversion of bind values to the JSON the Rust side expects, and of tagged
+ * result values back to JavaScript types.
+ */
+
+/**
+ * Bound parameters: positional (`[1, "a"]`, for `$1`/`?` placeholders) or
+ * named (`{ id: 1 }`, for `:id`/`@id`/`$id` placeholders). Named keys may
+ * include the prefix; a bare key binds whichever prefix the query uses.
+ * Keys the query doesn't use are ignored, and placeholders without a key
+ * bind `NULL`, as in SQLite.
+ *
+ * `Uint8Array`s and `ArrayBuffer`s are bound as blobs and `bigint`s as
+ * integers. Use `TypedValue`s (`blob()`, `int64()`, `real()`) to pick a
+ * type explicitly.
+ */
+export type BindValues = unknown[] | Record<string, unknown>;
+
+/**
+ * A bind value with an explicit SQLite type, for values plain JSON can't
+ * carry exactly. Invalid or out-of-range values are rejected by the plugin
+ * instead of being coerced.
+ */
+export type TypedValue =
+  | { $type: "blob"; base64: string }
+  | { $type: "blob"; value: number[] }
+  | { $type: "int64"; value: string | number }
+  | { $type: "real"; value: number | string }
+  | { $type: "text"; value: string }
+  | { $type: "null" };
+
+/** Binds bytes as a blob */
+export function blob(bytes: Uint8Array | ArrayBuffer): TypedValue {
+  const view = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes);
+  // Build the binary string in chunks to stay under argument-count limits
+  let binary = "";
+  for (let i = 0; i < view.length; i += 0x8000) {
+    binary += String.fromCharCode(...view.subarray(i, i + 0x8000));
+  }
+  return { $type: "blob", base64: btoa(binary) };
+}
+
+/** Binds a 64-bit integer, including ones beyond `Number.MAX_SAFE_INTEGER` */
+export function int64(value: bigint | number | string): TypedValue {
+  return { $type: "int64", value: String(value) };
+}
+
+/**
+ * Binds a number as REAL even when it has no fractional part. NaN and
+ * ±Infinity are bound per the `nonFinite` option.
+ */
+export function real(value: number): TypedValue {
+  return { $type: "real", value: Number.isFinite(value) ? value : String(value) };
+}
+
+/**
+ * Prepares bind values for IPC. `Uint8Array`s, other typed arrays and
+ * `ArrayBuffer`s would otherwise serialize as objects keyed by index,
+ * `bigint`s can't be serialized at all, and NaN and ±Infinity become null,
+ * so all of them are sent as `TypedValue`s.
+ */
+export function encodeBindValues(values: BindValues | undefined): BindValues {
+  if (values === undefined) return [];
+  if (Array.isArray(values)) return values.map(encodeValue);
+  return Object.fromEntries(
+    Object.entries(values).map(([key, value]) => [key, encodeValue(value)])
+  );
+}
+
+function encodeValue(value: unknown): unknown {
+  if (typeof value === "bigint") {
+    return int64(value);
+  }
+  if (typeof value === "number" && !Number.isFinite(value)) {
+    // JSON would turn these into null
+    return real(value);
+  }
+  if (value instanceof ArrayBuffer) {
+    return blob(value);
+  }
+  if (ArrayBuffer.isView(value)) {
+    return blob(new Uint8Array(value.buffer, value.byteOffset, value.byteLength));
+  }
+  return value;
+}
+
+/**
+ * Turns tagged result values back into JavaScript values, in place:
+ * `{ $type: "int64", value }` (from `integerEncoding: "bigint"`) into a
+ * `bigint`, `{ $type: "real", value }` (from `nonFinite: "tagged"`) into
+ * NaN or ±Infinity, and `{ $type: "blob", base64 }` (from
+ * `blobEncoding: "base64"`) into a `Uint8Array`, which `encodeBindValues`
+ * binds back as a blob. Accepts object rows, a columnar result, or a
+ * `batchParams()` result.
+ */
+export function reviveResult<T>(result: T): T {
+  if (Array.isArray(result)) {
+    for (const item of result) {
+      if (Array.isArray(item)) reviveResult(item);
+      else reviveRow(item);
+    }
+  } else if (isObject(result) && Array.isArray(result.rows)) {
+    for (const row of result.rows) reviveRow(row);
+  }
+  return result;
+}
+
+function reviveRow(row: unknown): void {
+  if (!isObject(row)) return;
+  for (const key of Object.keys(row)) {
+    const value = row[key];
+    if (!isObject(value)) continue;
+    if (value.$type === "blob" && typeof value.base64 === "string") {
+      row[key] = decodeBase64(value.base64);
+    } else if (typeof value.value === "string") {
+      if (value.$type === "int64") row[key] = BigInt(value.value);
+      else if (value.$type === "real") row[key] = Number(value.value);
+    }
+  }
+}
+
+function decodeBase64(base64: string): Uint8Array {
+  const binary = atob(base64);
+  const bytes = new Uint8Array(binary.length);
+  for (let i = 0; i < binary.length; i++) bytes[i] = binary.charCodeAt(i);
+  return bytes;
+}
+
+function isObject(value: unknown): value is Record<string, unknown> {
+  return typeof value === "object" && value !== null;
+}
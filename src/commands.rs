@@ -1,9 +1,18 @@
-use indexmap::IndexMap;
-use serde_json::Value as JsonValue;
-use tauri::{command, AppHandle, Manager, Runtime, State};
+use std::time::Duration;
+use tauri::{
+    command,
+    ipc::{Channel, Response},
+    AppHandle, Manager, Runtime, State,
+};
 
-use crate::models::{LoadOptions, PingRequest, PingResponse, QueryResult};
-use crate::wrapper::DbInstances;
+use crate::decode::Encodings;
+use crate::models::{
+    BackupEvent, BatchResult, BatchStatement, DumpResult, EncryptionConfig, ExportOptions,
+    ImportEvent, ImportOptions, ImportResult, LoadOptions, MigrateToResult, MigrationPlan,
+    MigrationStatus, PingRequest, PingResponse, QueryOptions, QueryResult, QueryValues, SchemaInfo,
+    ScriptResult, SelectResult, StatementResult, StreamEvent,
+};
+use crate::wrapper::{resolve_file_path, DbInstances, DEFAULT_STREAM_CHUNK_SIZE};
 use crate::Error;
 
 #[cfg(desktop)]
@@ -26,9 +35,14 @@ pub(crate) async fn load<R: Runtime>(
     // Use provided encryption, or fall back to plugin default
     let encryption = options.encryption.or_else(|| libsql.encryption().cloned());
 
+    let read_pool_size = options
+        .read_pool_size
+        .unwrap_or_else(|| libsql.read_pool_size());
+
     // Idempotent: if a connection for this path is already open, return it as-is
     // rather than silently replacing it (which would drop in-flight queries).
-    if db_instances.0.lock().await.contains_key(&path) {
+    // `get` waits for a restore of this path to finish.
+    if db_instances.get(&path).await.is_ok() {
         return Ok(path);
     }
 
@@ -38,9 +52,20 @@ pub(crate) async fn load<R: Runtime>(
         base_path,
         options.sync_url,
         options.auth_token,
+        read_pool_size,
+        Encodings {
+            blob: options.blob_encoding.unwrap_or_default(),
+            integer: options.integer_encoding.unwrap_or_default(),
+            non_finite: options.non_finite.unwrap_or_default(),
+        },
     )
     .await?;
 
+    let migrations = libsql.migrations(&path);
+    if !migrations.is_empty() {
+        conn.migrate(migrations).await?;
+    }
+
     db_instances
         .0
         .lock()
@@ -56,18 +81,11 @@ pub(crate) async fn execute(
     db_instances: State<'_, DbInstances>,
     db: String,
     query: String,
-    values: Vec<JsonValue>,
+    values: QueryValues,
+    transaction_id: Option<u64>,
 ) -> Result<QueryResult, Error> {
-    // Clone the Arc while holding the lock, then release the lock before
-    // awaiting the query so other operations aren't blocked.
-    let conn = {
-        let instances = db_instances.0.lock().await;
-        instances
-            .get(&db)
-            .ok_or_else(|| Error::DatabaseNotLoaded(db.clone()))?
-            .clone()
-    };
-    conn.execute(&query, values).await
+    let conn = db_instances.get(&db).await?;
+    conn.execute(&query, values, transaction_id).await
 }
 
 /// Execute a query that returns rows
@@ -76,18 +94,109 @@ pub(crate) async fn select(
     db_instances: State<'_, DbInstances>,
     db: String,
     query: String,
-    values: Vec<JsonValue>,
-) -> Result<Vec<IndexMap<String, JsonValue>>, Error> {
-    // Clone the Arc while holding the lock, then release the lock before
-    // awaiting the query so other operations aren't blocked.
-    let conn = {
-        let instances = db_instances.0.lock().await;
-        instances
-            .get(&db)
-            .ok_or_else(|| Error::DatabaseNotLoaded(db.clone()))?
-            .clone()
-    };
-    conn.select(&query, values).await
+    values: QueryValues,
+    transaction_id: Option<u64>,
+    options: Option<QueryOptions>,
+) -> Result<SelectResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.select(&query, values, transaction_id, &options.unwrap_or_default())
+        .await
+}
+
+/// Run any statement and return its rows together with `rowsAffected` and
+/// `lastInsertId`, leaving it to the statement whether it produces rows.
+#[command]
+pub(crate) async fn query(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    query: String,
+    values: QueryValues,
+    transaction_id: Option<u64>,
+    options: Option<QueryOptions>,
+) -> Result<StatementResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.query(&query, values, transaction_id, &options.unwrap_or_default())
+        .await
+}
+
+/// Execute a query that returns rows, encoded as MessagePack instead of JSON.
+/// The payload has the shape of a columnar `select` result; decode it with
+/// `decode` from `guest-js/msgpack.ts`.
+#[command]
+pub(crate) async fn select_binary(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    query: String,
+    values: QueryValues,
+    transaction_id: Option<u64>,
+    options: Option<QueryOptions>,
+) -> Result<Response, Error> {
+    let conn = db_instances.get(&db).await?;
+    let bytes = conn
+        .select_binary(&query, values, transaction_id, &options.unwrap_or_default())
+        .await?;
+    Ok(Response::new(bytes))
+}
+
+/// Run a read-only query and push its rows over `on_event` in chunks of
+/// `chunk_size`, instead of collecting them all first. Returns the stream id
+/// used to `ack_stream` each chunk (the stream pauses when the consumer falls
+/// behind) or to `cancel_stream` it.
+#[command]
+pub(crate) async fn select_stream(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    query: String,
+    values: QueryValues,
+    chunk_size: Option<usize>,
+    on_event: Channel<StreamEvent>,
+) -> Result<u64, Error> {
+    let conn = db_instances.get(&db).await?;
+    let chunk_size = chunk_size.unwrap_or(DEFAULT_STREAM_CHUNK_SIZE);
+    conn.select_stream(&query, values, chunk_size, move |event| {
+        on_event.send(event).is_ok()
+    })
+    .await
+}
+
+/// Acknowledge a `select_stream` chunk, letting the stream send another
+#[command]
+pub(crate) async fn ack_stream(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    stream_id: u64,
+) -> Result<(), Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.ack_stream(stream_id).await;
+    Ok(())
+}
+
+/// Stop a `select_stream` and drop its cursor
+#[command]
+pub(crate) async fn cancel_stream(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    stream_id: u64,
+) -> Result<(), Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.cancel_stream(stream_id).await;
+    Ok(())
+}
+
+/// Run a whole SQL script — e.g. a migration file — statement by statement.
+/// `;` inside string literals, comments and trigger bodies is handled. With
+/// `atomic`, the script runs in a transaction and a failure undoes all of it.
+#[command]
+pub(crate) async fn execute_script(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    script: String,
+    transaction_id: Option<u64>,
+    atomic: Option<bool>,
+) -> Result<ScriptResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.execute_script(&script, transaction_id, atomic.unwrap_or(false))
+        .await
 }
 
 /// Execute multiple SQL statements atomically inside a single transaction.
@@ -99,26 +208,68 @@ pub(crate) async fn batch(
     db: String,
     queries: Vec<String>,
 ) -> Result<(), Error> {
-    let conn = {
-        let instances = db_instances.0.lock().await;
-        instances
-            .get(&db)
-            .ok_or_else(|| Error::DatabaseNotLoaded(db.clone()))?
-            .clone()
-    };
+    let conn = db_instances.get(&db).await?;
     conn.batch(queries).await
 }
 
+/// Execute parameterized statements atomically inside a single transaction
+/// and return a result (or row set, for statements returning columns) for each,
+/// in order.
+#[command]
+pub(crate) async fn batch_params(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    statements: Vec<BatchStatement>,
+    transaction_id: Option<u64>,
+) -> Result<Vec<BatchResult>, Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.batch_params(statements, transaction_id).await
+}
+
+/// Begin an interactive transaction and return its id. Pass the id as
+/// `transactionId` to `execute`/`select`, then `commit` or `rollback` it.
+/// Transactions left open longer than `timeout_ms` (or the plugin's
+/// configured default) are rolled back automatically.
+#[command]
+pub(crate) async fn begin_transaction<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    timeout_ms: Option<u64>,
+) -> Result<u64, Error> {
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or_else(|| app.state::<Libsql>().transaction_timeout());
+    let conn = db_instances.get(&db).await?;
+    conn.begin_transaction(timeout).await
+}
+
+/// Commit an interactive transaction
+#[command]
+pub(crate) async fn commit(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    transaction_id: u64,
+) -> Result<(), Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.commit(transaction_id).await
+}
+
+/// Roll back an interactive transaction
+#[command]
+pub(crate) async fn rollback(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    transaction_id: u64,
+) -> Result<(), Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.rollback(transaction_id).await
+}
+
 /// Sync an embedded replica with its remote Turso database
 #[command]
 pub(crate) async fn sync(db_instances: State<'_, DbInstances>, db: String) -> Result<(), Error> {
-    let conn = {
-        let instances = db_instances.0.lock().await;
-        instances
-            .get(&db)
-            .ok_or_else(|| Error::DatabaseNotLoaded(db.clone()))?
-            .clone()
-    };
+    let conn = db_instances.get(&db).await?;
     conn.sync().await
 }
 
@@ -144,6 +295,170 @@ pub(crate) async fn close(
     Ok(true)
 }
 
+/// Move `db` to migration `version`: revert the applied migrations above it
+/// using their down scripts, then apply pending ones up to it. Until it is
+/// moved to the newest version again, `load` and `restore` stop at `version`.
+#[command]
+pub(crate) async fn migrate_to<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    version: u64,
+) -> Result<MigrateToResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    let libsql = app.state::<Libsql>().inner();
+    conn.migrate_to(libsql.migrations(&db), version).await
+}
+
+/// Write a plain-SQL dump of `db` — `CREATE` statements and `INSERT`s, in
+/// dependency order — to `path`, relative to the plugin base path
+#[command]
+pub(crate) async fn export_sql<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    path: String,
+) -> Result<DumpResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    let path = resolve_file_path(&path, &app.state::<Libsql>().base_path())?;
+    db_instances.check_not_live(&path).await?;
+    conn.export_sql(&path).await
+}
+
+/// Restore a dump from `path`, relative to the plugin base path, into `db`,
+/// which must be empty
+#[command]
+pub(crate) async fn import_sql<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    path: String,
+) -> Result<ScriptResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    let path = resolve_file_path(&path, &app.state::<Libsql>().base_path())?;
+    conn.import_sql(&path).await
+}
+
+/// Run a query on `db` and write its rows to `path`, relative to the plugin
+/// base path, as CSV or NDJSON without collecting them first. Returns the
+/// number of rows written.
+#[command]
+pub(crate) async fn export_query<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    query: String,
+    values: QueryValues,
+    path: String,
+    options: Option<ExportOptions>,
+) -> Result<u64, Error> {
+    let conn = db_instances.get(&db).await?;
+    let path = resolve_file_path(&path, &app.state::<Libsql>().base_path())?;
+    db_instances.check_not_live(&path).await?;
+    conn.export_query(&query, values, &path, &options.unwrap_or_default())
+        .await
+}
+
+/// Write a consistent copy of `db` to `path`, relative to the plugin base
+/// path, encrypted with `encryption` or else like `db`. Progress is pushed
+/// over `on_event`. Returns the size of the backup in bytes.
+#[command]
+pub(crate) async fn backup<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    path: String,
+    encryption: Option<EncryptionConfig>,
+    on_event: Channel<BackupEvent>,
+) -> Result<u64, Error> {
+    let conn = db_instances.get(&db).await?;
+    let path = resolve_file_path(&path, &app.state::<Libsql>().base_path())?;
+    db_instances.check_not_live(&path).await?;
+    conn.backup(&path, encryption, move |event| {
+        let _ = on_event.send(event);
+    })
+    .await
+}
+
+/// Replace `db` with the backup at `path`, relative to the plugin base path,
+/// and reopen it. `encryption` is the backup's key, if it differs from the
+/// database's.
+#[command]
+pub(crate) async fn restore<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    path: String,
+    encryption: Option<EncryptionConfig>,
+) -> Result<(), Error> {
+    let libsql = app.state::<Libsql>().inner();
+    let path = resolve_file_path(&path, &libsql.base_path())?;
+    db_instances
+        .restore(&db, &path, encryption, libsql.migrations(&db))
+        .await
+}
+
+/// Import a CSV or NDJSON file at `path`, relative to the plugin base path,
+/// into `table` in one transaction. Progress and skipped records are pushed
+/// over `on_event`.
+#[command]
+pub(crate) async fn import_file<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    path: String,
+    table: String,
+    options: Option<ImportOptions>,
+    on_event: Channel<ImportEvent>,
+) -> Result<ImportResult, Error> {
+    let conn = db_instances.get(&db).await?;
+    let path = resolve_file_path(&path, &app.state::<Libsql>().base_path())?;
+    conn.import_file(&path, &table, &options.unwrap_or_default(), move |event| {
+        let _ = on_event.send(event);
+    })
+    .await
+}
+
+/// Describe the schema of `db`: tables with their columns, indexes and
+/// foreign keys, views and triggers, for `main` and every attached database
+#[command]
+pub(crate) async fn introspect(
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    transaction_id: Option<u64>,
+) -> Result<Vec<SchemaInfo>, Error> {
+    let conn = db_instances.get(&db).await?;
+    conn.introspect(transaction_id).await
+}
+
+/// Dry-run `migrate_to(version)`, or applying every pending migration when
+/// `version` is omitted: report the migrations and statements that would run
+/// and the resulting schema changes, then roll everything back
+#[command]
+pub(crate) async fn migration_plan<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+    version: Option<u64>,
+) -> Result<MigrationPlan, Error> {
+    let conn = db_instances.get(&db).await?;
+    let libsql = app.state::<Libsql>().inner();
+    conn.migration_plan(libsql.migrations(&db), version).await
+}
+
+/// List the migrations configured for `db` in `Config::migrations`, plus any
+/// others recorded in its ledger, as applied, pending or modified
+#[command]
+pub(crate) async fn migration_status<R: Runtime>(
+    app: AppHandle<R>,
+    db_instances: State<'_, DbInstances>,
+    db: String,
+) -> Result<Vec<MigrationStatus>, Error> {
+    let conn = db_instances.get(&db).await?;
+    let libsql = app.state::<Libsql>().inner();
+    conn.migration_status(libsql.migrations(&db)).await
+}
+
 /// Ping command (for backwards compatibility)
 #[command]
 pub(crate) async fn ping<R: Runtime>(
@@ -1,12 +1,144 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use libsql::{Row, Value};
-use serde_json::{Number, Value as JsonValue};
+use serde_json::{json, Number, Value as JsonValue};
 
+use crate::models::{BlobEncoding, ColumnMetadata, IntegerEncoding, NonFinitePolicy, QueryOptions};
+use crate::msgpack::Encoder;
 use crate::Error;
 
-/// Convert a libsql row column to a JSON value
-pub fn to_json(row: &Row, idx: i32) -> Result<JsonValue, Error> {
-    let value = row.get_value(idx)?;
-    value_to_json(value)
+/// Largest integer JavaScript represents exactly (`Number.MAX_SAFE_INTEGER`)
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// How a connection writes values that JSON can't carry as-is, unless a
+/// query overrides it
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Encodings {
+    pub blob: BlobEncoding,
+    pub integer: IntegerEncoding,
+    pub non_finite: NonFinitePolicy,
+}
+
+impl Encodings {
+    /// These encodings with any overrides from `options` applied
+    pub fn with_overrides(self, options: &QueryOptions) -> Self {
+        Self {
+            blob: options.blob_encoding.unwrap_or(self.blob),
+            integer: options.integer_encoding.unwrap_or(self.integer),
+            non_finite: options.non_finite.unwrap_or(self.non_finite),
+        }
+    }
+}
+
+/// Converts the columns of one result set to JSON values
+#[derive(Debug, Default)]
+pub struct Decoder {
+    /// Declared type of each column. Empty to convert by storage class alone.
+    decl_types: Vec<Option<String>>,
+    /// Whether each column encodes every integer per `IntegerEncoding`
+    int64_columns: Vec<bool>,
+    /// Write base64 blobs as bare strings rather than in the `blob` envelope
+    bare_blobs: bool,
+    encodings: Encodings,
+}
+
+impl Decoder {
+    pub fn new(encodings: Encodings) -> Self {
+        Self {
+            encodings,
+            ..Self::default()
+        }
+    }
+
+    /// Also consult each column's declared type: integers in `BOOLEAN`/`BOOL`
+    /// columns become `true`/`false`. No other declared type changes a value.
+    pub fn typed(mut self, columns: &[ColumnMetadata]) -> Self {
+        self.decl_types = columns.iter().map(|c| c.decl_type.clone()).collect();
+        self
+    }
+
+    /// Encode every integer in the columns named `names` per the
+    /// `IntegerEncoding`, not just those outside the safe range.
+    pub fn int64_columns(mut self, columns: &[ColumnMetadata], names: &[String]) -> Self {
+        self.int64_columns = columns.iter().map(|c| names.contains(&c.name)).collect();
+        self
+    }
+
+    /// Write base64 blobs as bare strings, for output that is never bound
+    /// back (e.g. files), instead of `{ "$type": "blob", "base64" }`.
+    pub fn bare_blobs(mut self) -> Self {
+        self.bare_blobs = true;
+        self
+    }
+
+    /// Convert a libsql row column to a JSON value
+    pub fn to_json(&self, row: &Row, idx: i32) -> Result<JsonValue, Error> {
+        let value = row.get_value(idx)?;
+        let decl_type = self.decl_types.get(idx as usize).and_then(Option::as_deref);
+
+        match (value, decl_type) {
+            (Value::Integer(i), Some(t)) if is_boolean(t) => Ok(JsonValue::Bool(i != 0)),
+            (Value::Integer(i), _) if self.exceeds_number(idx, i) => Ok(self.integer_to_json(i)),
+            (Value::Real(f), _) if !f.is_finite() => self.non_finite_to_json(f),
+            (Value::Blob(bytes), _) if self.encodings.blob == BlobEncoding::Base64 => {
+                let base64 = BASE64.encode(bytes);
+                if self.bare_blobs {
+                    Ok(JsonValue::String(base64))
+                } else {
+                    Ok(json!({ "$type": "blob", "base64": base64 }))
+                }
+            }
+            (value, _) => value_to_json(value),
+        }
+    }
+
+    /// Append a libsql row column to a MessagePack buffer. Blobs are always
+    /// written as raw bytes.
+    pub fn to_msgpack(&self, row: &Row, idx: i32, encoder: &mut Encoder) -> Result<(), Error> {
+        let value = row.get_value(idx)?;
+        let decl_type = self.decl_types.get(idx as usize).and_then(Option::as_deref);
+
+        match (value, decl_type) {
+            (Value::Integer(i), Some(t)) if is_boolean(t) => encoder.bool(i != 0),
+            (value, _) => encoder.value(&value),
+        }
+        Ok(())
+    }
+
+    /// Whether integer `i` in column `idx` must not be written as a number
+    fn exceeds_number(&self, idx: i32, i: i64) -> bool {
+        self.encodings.integer != IntegerEncoding::Number
+            && (!(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&i)
+                || self.int64_columns.get(idx as usize) == Some(&true))
+    }
+
+    fn integer_to_json(&self, i: i64) -> JsonValue {
+        match self.encodings.integer {
+            IntegerEncoding::Number => JsonValue::Number(Number::from(i)),
+            IntegerEncoding::String => JsonValue::String(i.to_string()),
+            IntegerEncoding::BigInt => json!({ "$type": "int64", "value": i.to_string() }),
+        }
+    }
+
+    fn non_finite_to_json(&self, f: f64) -> Result<JsonValue, Error> {
+        let name = if f.is_nan() {
+            "NaN"
+        } else if f > 0.0 {
+            "Infinity"
+        } else {
+            "-Infinity"
+        };
+        match self.encodings.non_finite {
+            NonFinitePolicy::Error => Err(Error::UnsupportedDatatype(format!(
+                "Invalid float value: {name} (see the `nonFinite` option)"
+            ))),
+            NonFinitePolicy::Null => Ok(JsonValue::Null),
+            NonFinitePolicy::Tagged => Ok(json!({ "$type": "real", "value": name })),
+        }
+    }
+}
+
+fn is_boolean(decl_type: &str) -> bool {
+    decl_type.eq_ignore_ascii_case("boolean") || decl_type.eq_ignore_ascii_case("bool")
 }
 
 /// Convert a libsql Value to a JSON value
@@ -1,7 +1,10 @@
 use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::time::Duration;
 use tauri::{plugin::PluginApi, AppHandle, Runtime};
 
+use crate::migrate::Migration;
 use crate::models::*;
 
 /// Plugin configuration
@@ -12,6 +15,19 @@ pub struct Config {
     /// Default encryption configuration for all databases.
     /// Can be overridden per-database when loading.
     pub encryption: Option<EncryptionConfig>,
+    /// How long an interactive transaction may stay open before it is rolled
+    /// back automatically. Defaults to 30 seconds.
+    pub transaction_timeout: Option<Duration>,
+    /// Number of read-only connections opened alongside the writer for each
+    /// local database file, so reads don't queue behind writes. The database
+    /// is switched to WAL mode, for good, when this is non-zero. Defaults to
+    /// 0 (no pool); at most 16.
+    pub read_pool_size: Option<usize>,
+    /// Migrations applied by `load`, keyed by database path as passed to it
+    /// (e.g. `"sqlite:app.db"`). Pending migrations run before `load`
+    /// returns — up to the version `migrate_to` last moved the database
+    /// back to, if any; if one fails, the database isn't loaded.
+    pub migrations: HashMap<String, Vec<Migration>>,
 }
 
 pub fn init<R: Runtime, C: DeserializeOwned>(
@@ -44,4 +60,23 @@ impl Libsql {
     pub fn encryption(&self) -> Option<&EncryptionConfig> {
         self.0.encryption.as_ref()
     }
+
+    /// Get the number of read connections opened per local database
+    pub fn read_pool_size(&self) -> usize {
+        self.0
+            .read_pool_size
+            .unwrap_or(crate::wrapper::DEFAULT_READ_POOL_SIZE)
+    }
+
+    /// Get the migrations to run when the database at `path` is loaded
+    pub fn migrations(&self, path: &str) -> &[Migration] {
+        self.0.migrations.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get how long an interactive transaction may stay open
+    pub fn transaction_timeout(&self) -> Duration {
+        self.0
+            .transaction_timeout
+            .unwrap_or(crate::wrapper::DEFAULT_TRANSACTION_TIMEOUT)
+    }
 }
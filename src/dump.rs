@@ -0,0 +1,374 @@
+//! Plain-SQL dumps: the schema as `CREATE` statements and the data as
+//! `INSERT`s, in the format of the `sqlite3` shell's `.dump`, so a dump can
+//! also be restored with `sqlite3 new.db < dump.sql`.
+
+use libsql::{params::Params, Connection, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, Write};
+
+use crate::models::{DumpResult, ScriptResult};
+use crate::script::{is_complete, quote_identifier};
+use crate::wrapper::run_script;
+use crate::Error;
+
+/// A `sqlite_master` entry
+struct SchemaObject {
+    object_type: String,
+    name: String,
+    sql: String,
+}
+
+/// Write a dump of the `main` schema to `out`, one statement per line.
+///
+/// Tables come first, ordered so that tables referenced by foreign keys
+/// precede the tables referencing them, then their rows, then indexes, views
+/// and triggers. Rows are written as they are read. Run this inside a read
+/// transaction for a consistent snapshot.
+pub(crate) async fn export(conn: &Connection, out: &mut impl Write) -> Result<DumpResult, Error> {
+    let mut objects = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT type, name, sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY rowid",
+            Params::None,
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        objects.push(SchemaObject {
+            object_type: row.get(0)?,
+            name: row.get(1)?,
+            sql: row.get(2)?,
+        });
+    }
+
+    // Shadow tables (e.g. an FTS index's) are recreated by their virtual table
+    let mut shadow = HashSet::new();
+    let mut rows = conn
+        .query(
+            "SELECT name FROM pragma_table_list WHERE schema = 'main' AND type = 'shadow'",
+            Params::None,
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        shadow.insert(row.get::<String>(0)?);
+    }
+
+    let tables: Vec<_> = objects
+        .iter()
+        .filter(|o| {
+            o.object_type == "table" && !o.name.starts_with("sqlite_") && !shadow.contains(&o.name)
+        })
+        .collect();
+    let tables = in_dependency_order(conn, tables).await?;
+
+    writeln!(out, "PRAGMA foreign_keys=OFF;")?;
+    writeln!(out, "BEGIN TRANSACTION;")?;
+    for table in &tables {
+        writeln!(out, "{};", table.sql)?;
+    }
+
+    let mut total_rows = 0;
+    for table in &tables {
+        total_rows += dump_rows(conn, &table.name, out).await?;
+    }
+    if objects.iter().any(|o| o.name == "sqlite_sequence") {
+        writeln!(out, "DELETE FROM sqlite_sequence;")?;
+        dump_rows(conn, "sqlite_sequence", out).await?;
+    }
+
+    for object_type in ["index", "view", "trigger"] {
+        for object in objects.iter().filter(|o| o.object_type == object_type) {
+            writeln!(out, "{};", object.sql)?;
+        }
+    }
+    writeln!(out, "COMMIT;")?;
+    out.flush()?;
+
+    Ok(DumpResult {
+        tables: tables.len(),
+        rows: total_rows,
+    })
+}
+
+/// Sort `tables` so each comes after the tables its foreign keys reference.
+/// Cycles are broken by the original (creation) order.
+async fn in_dependency_order<'a>(
+    conn: &Connection,
+    tables: Vec<&'a SchemaObject>,
+) -> Result<Vec<&'a SchemaObject>, Error> {
+    let mut references = HashMap::new();
+    for table in &tables {
+        let mut rows = conn
+            .query(
+                "SELECT DISTINCT \"table\" FROM pragma_foreign_key_list(?1)",
+                [table.name.as_str()],
+            )
+            .await?;
+        let mut referenced = Vec::new();
+        while let Some(row) = rows.next().await? {
+            referenced.push(row.get::<String>(0)?);
+        }
+        references.insert(table.name.as_str(), referenced);
+    }
+
+    fn visit<'a>(
+        table: &'a SchemaObject,
+        by_name: &HashMap<&str, &'a SchemaObject>,
+        references: &HashMap<&str, Vec<String>>,
+        seen: &mut HashSet<&'a str>,
+        ordered: &mut Vec<&'a SchemaObject>,
+    ) {
+        if !seen.insert(&table.name) {
+            return;
+        }
+        for name in &references[table.name.as_str()] {
+            // Table names are case-insensitive
+            let referenced = by_name
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, t)| *t);
+            if let Some(referenced) = referenced {
+                visit(referenced, by_name, references, seen, ordered);
+            }
+        }
+        ordered.push(table);
+    }
+
+    let by_name: HashMap<_, _> = tables.iter().map(|t| (t.name.as_str(), *t)).collect();
+    let mut seen = HashSet::new();
+    let mut ordered = Vec::with_capacity(tables.len());
+    for table in &tables {
+        visit(table, &by_name, &references, &mut seen, &mut ordered);
+    }
+    Ok(ordered)
+}
+
+/// Write an `INSERT` per row of `table`. Generated columns are left out,
+/// since they can't be inserted.
+async fn dump_rows(conn: &Connection, table: &str, out: &mut impl Write) -> Result<u64, Error> {
+    let mut columns = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT name FROM pragma_table_xinfo(?1) WHERE hidden = 0 ORDER BY cid",
+            [table],
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        columns.push(quote_identifier(&row.get::<String>(0)?));
+    }
+    let columns = columns.join(",");
+    let table = quote_identifier(table);
+
+    let mut rows = conn
+        .query(&format!("SELECT {columns} FROM {table}"), Params::None)
+        .await?;
+    let column_count = rows.column_count();
+    let mut count = 0;
+    let mut line = String::new();
+    while let Some(row) = rows.next().await? {
+        line.clear();
+        line.push_str(&format!("INSERT INTO {table}({columns}) VALUES("));
+        for idx in 0..column_count {
+            if idx > 0 {
+                line.push(',');
+            }
+            push_literal(&mut line, &row.get_value(idx)?);
+        }
+        line.push_str(");");
+        writeln!(out, "{line}")?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Append `value` as an SQL literal that reads back as the same value and type
+fn push_literal(sql: &mut String, value: &Value) {
+    match value {
+        Value::Null => sql.push_str("NULL"),
+        Value::Integer(i) => sql.push_str(&i.to_string()),
+        // SQLite stores NaN as NULL
+        Value::Real(f) if f.is_nan() => sql.push_str("NULL"),
+        Value::Real(f) if f.is_infinite() => {
+            sql.push_str(if *f > 0.0 { "9e999" } else { "-9e999" })
+        }
+        // Shortest round-tripping form; always has a `.` or exponent, so
+        // whole numbers stay REAL
+        Value::Real(f) => sql.push_str(&format!("{f:?}")),
+        Value::Text(s) => {
+            sql.push('\'');
+            sql.push_str(&s.replace('\'', "''"));
+            sql.push('\'');
+        }
+        Value::Blob(bytes) => {
+            sql.push_str("X'");
+            for b in bytes {
+                sql.push_str(&format!("{b:02X}"));
+            }
+            sql.push('\'');
+        }
+    }
+}
+
+/// Run a dump read from `input` against an empty database. Statements run as
+/// soon as they have been read, so the dump is never held in memory whole.
+///
+/// A dump from `export` restores atomically: it runs in the transaction it
+/// opens itself, which is rolled back if a statement fails. The connection's
+/// `foreign_keys` setting is restored afterwards.
+pub(crate) async fn import(conn: &Connection, input: impl BufRead) -> Result<ScriptResult, Error> {
+    let mut rows = conn
+        .query(
+            "SELECT 1 FROM sqlite_master WHERE name NOT LIKE 'sqlite\\_%' ESCAPE '\\'",
+            Params::None,
+        )
+        .await?;
+    if rows.next().await?.is_some() {
+        return Err(Error::OperationNotSupported(
+            "a dump can only be imported into an empty database".into(),
+        ));
+    }
+    drop(rows);
+
+    let foreign_keys: i64 = conn
+        .query("PRAGMA foreign_keys", Params::None)
+        .await?
+        .next()
+        .await?
+        .map_or(Ok(0), |row| row.get(0))?;
+
+    let result = run_dump(conn, input).await;
+    if !conn.is_autocommit() {
+        let _ = conn.execute("ROLLBACK", Params::None).await;
+    }
+    let _ = conn
+        .execute(&format!("PRAGMA foreign_keys={foreign_keys}"), Params::None)
+        .await;
+
+    result
+}
+
+/// Run `input` a chunk of complete statements at a time. Returns the number
+/// of statements run and rows they changed; errors report their position in
+/// the whole input.
+async fn run_dump(conn: &Connection, mut input: impl BufRead) -> Result<ScriptResult, Error> {
+    let mut total = ScriptResult::default();
+    let mut chunk = String::new();
+    // Byte offset and line number of the chunk in the input
+    let mut offset = 0;
+    let mut line = 1;
+
+    loop {
+        let len = chunk.len();
+        let read = input.read_line(&mut chunk)?;
+        let at_end = read == 0;
+        let complete = chunk[len..].contains(';') && is_complete(&chunk);
+        if !(at_end || complete) {
+            continue;
+        }
+
+        let result = run_script(conn, &chunk).await.map_err(|e| match e {
+            Error::ScriptStatement {
+                index,
+                offset: statement_offset,
+                line: statement_line,
+                source,
+            } => Error::ScriptStatement {
+                index: total.statements + index,
+                offset: offset + statement_offset,
+                line: line + statement_line - 1,
+                source,
+            },
+            e => e,
+        })?;
+        total.statements += result.statements;
+        total.rows_affected += result.rows_affected;
+        if at_end {
+            return Ok(total);
+        }
+        offset += chunk.len();
+        line += chunk.matches('\n').count();
+        chunk.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, memory};
+
+    #[test]
+    fn dump_restores_schema_and_values_exactly() {
+        block_on(async {
+            let conn = memory().await;
+            conn.execute_batch(
+                "CREATE TABLE posts (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     user_id INTEGER REFERENCES users (id),
+                     body TEXT,
+                     score REAL,
+                     data BLOB,
+                     slug TEXT GENERATED ALWAYS AS (lower(body))
+                 );
+                 CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);
+                 CREATE INDEX posts_user ON posts (user_id);
+                 CREATE TRIGGER users_touch AFTER UPDATE ON users BEGIN SELECT 1; END;
+                 INSERT INTO users VALUES (1, 'O''Brien');
+                 INSERT INTO posts (user_id, body, score, data)
+                     VALUES (1, 'a;b' || char(10) || 'c', 2.0, x'00ff'), (1, NULL, 1e300, NULL);",
+            )
+            .await
+            .unwrap();
+
+            let mut dump = Vec::new();
+            let result = export(&conn, &mut dump).await.unwrap();
+            assert_eq!((result.tables, result.rows), (2, 3));
+            let dump = String::from_utf8(dump).unwrap();
+            // `users` is referenced by `posts`, so it's created first
+            assert!(dump.find("CREATE TABLE users") < dump.find("CREATE TABLE posts"));
+
+            let restored = memory().await;
+            import(&restored, dump.as_bytes()).await.unwrap();
+
+            let query = "SELECT id, user_id, body, score, typeof(score), hex(data), slug FROM posts
+                         UNION ALL SELECT seq, NULL, NULL, NULL, NULL, NULL, NULL FROM sqlite_sequence";
+            let read = |conn: Connection| async move {
+                let mut rows = conn.query(query, Params::None).await.unwrap();
+                let mut values = Vec::new();
+                while let Some(row) = rows.next().await.unwrap() {
+                    for idx in 0..7 {
+                        values.push(row.get_value(idx).unwrap());
+                    }
+                }
+                values
+            };
+            assert_eq!(read(restored.clone()).await, read(conn).await);
+
+            // A second import fails: the database is no longer empty
+            assert!(import(&restored, dump.as_bytes()).await.is_err());
+        });
+    }
+
+    #[test]
+    fn failed_import_rolls_back_and_reports_the_line() {
+        block_on(async {
+            let conn = memory().await;
+            let dump = "BEGIN TRANSACTION;\nCREATE TABLE t (x);\nINSERT INTO t VALUES (1);\n\
+                        INSERT INTO nope VALUES (2);\nCOMMIT;\n";
+
+            let err = import(&conn, dump.as_bytes()).await.unwrap_err();
+            assert!(matches!(
+                err,
+                Error::ScriptStatement {
+                    index: 3,
+                    line: 4,
+                    ..
+                }
+            ));
+            let mut rows = conn
+                .query("SELECT count(*) FROM sqlite_master", Params::None)
+                .await
+                .unwrap();
+            let count: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+            assert_eq!(count, 0);
+        });
+    }
+}
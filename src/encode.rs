@@ -0,0 +1,318 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use libsql::{params::Params, Value};
+use serde_json::{Map, Value as JsonValue};
+
+use crate::decode::Encodings;
+use crate::models::{BlobEncoding, NonFinitePolicy, QueryValues};
+use crate::Error;
+
+/// Convert JSON values to libsql params
+pub fn json_to_params(values: QueryValues, encodings: Encodings) -> Result<Params, Error> {
+    match values {
+        QueryValues::Positional(values) if values.is_empty() => Ok(Params::None),
+        QueryValues::Positional(values) => Ok(Params::Positional(
+            values
+                .into_iter()
+                .map(|v| json_to_libsql_value(v, encodings))
+                .collect::<Result<_, _>>()?,
+        )),
+        QueryValues::Named(values) if values.is_empty() => Ok(Params::None),
+        QueryValues::Named(values) => {
+            let mut params = Vec::with_capacity(values.len());
+            for (name, value) in values {
+                let value = json_to_libsql_value(value, encodings)?;
+                if name.starts_with([':', '@', '$']) {
+                    params.push((name, value));
+                } else {
+                    // SQLite ignores names a statement doesn't use, so a bare
+                    // key can bind all three prefixes.
+                    params.push((format!("@{name}"), value.clone()));
+                    params.push((format!("${name}"), value.clone()));
+                    params.push((format!(":{name}"), value));
+                }
+            }
+            Ok(Params::Named(params))
+        }
+    }
+}
+
+/// Convert a JSON value to a libsql value. Values that can't be represented
+/// exactly are rejected rather than coerced.
+pub(crate) fn json_to_libsql_value(v: JsonValue, encodings: Encodings) -> Result<Value, Error> {
+    match v {
+        JsonValue::Null => Ok(Value::Null),
+        JsonValue::Bool(b) => Ok(Value::Integer(if b { 1 } else { 0 })),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Ok(Value::Integer(i))
+            } else if n.is_u64() {
+                Err(Error::UnsupportedDatatype(format!(
+                    "integer {n} is out of range for a 64-bit signed integer"
+                )))
+            } else {
+                // Only non-integers are left; serde_json rejects non-finite numbers
+                Ok(Value::Real(n.as_f64().unwrap_or_default()))
+            }
+        }
+        JsonValue::String(s) => Ok(Value::Text(s)),
+        JsonValue::Array(arr)
+            if encodings.blob == BlobEncoding::Array && arr.iter().all(JsonValue::is_number) =>
+        {
+            bytes_from_array(&arr).map(Value::Blob)
+        }
+        JsonValue::Object(map) if map.contains_key("$type") => typed_value(&map, encodings),
+        JsonValue::Array(_) | JsonValue::Object(_) => Ok(Value::Text(v.to_string())),
+    }
+}
+
+/// Convert a typed-value envelope, `{ "$type": "…", … }`:
+///
+/// - `blob`: `base64` string, or `value` array of bytes
+/// - `int64`: `value` integer, or a decimal string for integers beyond 2^53
+/// - `real`: `value` number or numeric string, including `"NaN"`,
+///   `"Infinity"` and `"-Infinity"`, which are bound per `NonFinitePolicy`
+/// - `text`: `value` string
+/// - `null`
+fn typed_value(map: &Map<String, JsonValue>, encodings: Encodings) -> Result<Value, Error> {
+    let value = map.get("value");
+    match map.get("$type").and_then(JsonValue::as_str) {
+        Some("blob") => match (map.get("base64"), value) {
+            (Some(JsonValue::String(encoded)), None) => BASE64
+                .decode(encoded)
+                .map(Value::Blob)
+                .map_err(|e| invalid("blob", format!("invalid base64: {e}"))),
+            (None, Some(JsonValue::Array(arr))) => bytes_from_array(arr).map(Value::Blob),
+            _ => Err(invalid(
+                "blob",
+                "expected a `base64` string or a `value` array of bytes",
+            )),
+        },
+        Some("int64") => match value {
+            Some(JsonValue::Number(n)) => n
+                .as_i64()
+                .map(Value::Integer)
+                .ok_or_else(|| invalid("int64", format!("{n} is not a 64-bit signed integer"))),
+            Some(JsonValue::String(s)) => s
+                .parse()
+                .map(Value::Integer)
+                .map_err(|_| invalid("int64", format!("{s:?} is not a 64-bit signed integer"))),
+            _ => Err(invalid("int64", "expected a `value` integer or string")),
+        },
+        Some("real") => {
+            let f = match value {
+                Some(JsonValue::Number(n)) => n.as_f64(),
+                Some(JsonValue::String(s)) => s.parse::<f64>().ok(),
+                _ => None,
+            };
+            match (f, encodings.non_finite) {
+                (Some(f), _) if f.is_finite() => Ok(Value::Real(f)),
+                (Some(_), NonFinitePolicy::Null) => Ok(Value::Null),
+                (Some(f), NonFinitePolicy::Tagged) => Ok(Value::Real(f)),
+                (Some(f), NonFinitePolicy::Error) => Err(invalid(
+                    "real",
+                    format!("{f} is not finite (see the `nonFinite` option)"),
+                )),
+                (None, _) => Err(invalid("real", "expected a `value` number or string")),
+            }
+        }
+        Some("text") => match value {
+            Some(JsonValue::String(s)) => Ok(Value::Text(s.clone())),
+            _ => Err(invalid("text", "expected a `value` string")),
+        },
+        Some("null") => Ok(Value::Null),
+        Some(other) => Err(Error::UnsupportedDatatype(format!(
+            "unknown value type `{other}`"
+        ))),
+        None => Err(Error::UnsupportedDatatype(
+            "`$type` must be a string".into(),
+        )),
+    }
+}
+
+/// Blob bytes from an array of numbers, each of which must be in 0..=255
+fn bytes_from_array(arr: &[JsonValue]) -> Result<Vec<u8>, Error> {
+    arr.iter()
+        .map(|v| {
+            v.as_u64()
+                .and_then(|n| u8::try_from(n).ok())
+                .ok_or_else(|| invalid("blob", format!("byte {v} is not an integer in 0..=255")))
+        })
+        .collect()
+}
+
+fn invalid(value_type: &str, reason: impl std::fmt::Display) -> Error {
+    Error::UnsupportedDatatype(format!("invalid {value_type} value: {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Decoder;
+    use crate::models::IntegerEncoding;
+    use crate::testing::{block_on, memory};
+    use serde_json::json;
+
+    fn convert(v: JsonValue) -> Result<Value, Error> {
+        json_to_libsql_value(v, Encodings::default())
+    }
+
+    #[test]
+    fn typed_envelopes_bind_exact_values() {
+        assert_eq!(
+            convert(json!({ "$type": "int64", "value": "9007199254740993" })).unwrap(),
+            Value::Integer(9007199254740993)
+        );
+        assert_eq!(
+            convert(json!({ "$type": "real", "value": 1 })).unwrap(),
+            Value::Real(1.0)
+        );
+        assert_eq!(
+            convert(json!({ "$type": "blob", "base64": "AP8Q" })).unwrap(),
+            Value::Blob(vec![0, 255, 16])
+        );
+        assert_eq!(
+            convert(json!({ "$type": "text", "value": "[1]" })).unwrap(),
+            Value::Text("[1]".into())
+        );
+    }
+
+    #[test]
+    fn non_finite_reals_follow_policy() {
+        let nan = || json!({ "$type": "real", "value": "NaN" });
+        let with = |non_finite| Encodings {
+            non_finite,
+            ..Encodings::default()
+        };
+
+        assert!(convert(nan()).is_err());
+        assert_eq!(
+            json_to_libsql_value(nan(), with(NonFinitePolicy::Null)).unwrap(),
+            Value::Null
+        );
+        assert_eq!(
+            json_to_libsql_value(
+                json!({ "$type": "real", "value": "-Infinity" }),
+                with(NonFinitePolicy::Tagged)
+            )
+            .unwrap(),
+            Value::Real(f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn lossy_values_are_rejected() {
+        for v in [
+            json!([300, 1]),
+            json!([1.5]),
+            json!([-1]),
+            json!(u64::MAX),
+            json!({ "$type": "int64", "value": "9223372036854775808" }),
+            json!({ "$type": "int64", "value": 1.5 }),
+            json!({ "$type": "real", "value": "1e400" }),
+            json!({ "$type": "blob", "base64": "!!" }),
+            json!({ "$type": "bigint", "value": "1" }),
+        ] {
+            assert!(
+                matches!(convert(v.clone()), Err(Error::UnsupportedDatatype(_))),
+                "{v} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn decoded_results_bind_back_to_the_same_values() {
+        block_on(async {
+            let conn = memory().await;
+            let decode = |value: Value, encodings| {
+                let conn = &conn;
+                async move {
+                    let mut rows = conn.query("SELECT ?1", [value]).await.unwrap();
+                    let row = rows.next().await.unwrap().unwrap();
+                    Decoder::new(encodings).to_json(&row, 0).unwrap()
+                }
+            };
+            let with = |blob, integer| Encodings {
+                blob,
+                integer,
+                non_finite: NonFinitePolicy::Tagged,
+            };
+            let array = with(BlobEncoding::Array, IntegerEncoding::BigInt);
+            let base64 = with(BlobEncoding::Base64, IntegerEncoding::String);
+
+            for value in [
+                Value::Blob(vec![0, 1, 127, 128, 255]),
+                Value::Integer(i64::MIN),
+                Value::Integer(i64::MAX),
+                Value::Real(f64::INFINITY),
+                Value::Text("AP8Q".into()),
+            ] {
+                let json = decode(value.clone(), array).await;
+                assert_eq!(json_to_libsql_value(json, array).unwrap(), value);
+            }
+
+            // A base64 blob comes in its envelope, so it binds back as a blob;
+            // the bare string is bound as text like any other string
+            let bytes = vec![0, 255, 16];
+            let json = decode(Value::Blob(bytes.clone()), base64).await;
+            assert_eq!(json, json!({ "$type": "blob", "base64": "AP8Q" }));
+            assert_eq!(
+                json_to_libsql_value(json, base64).unwrap(),
+                Value::Blob(bytes)
+            );
+            assert_eq!(
+                json_to_libsql_value(json!("AP8Q"), base64).unwrap(),
+                Value::Text("AP8Q".into())
+            );
+
+            // Likewise a string-encoded integer needs the `int64` envelope
+            let json = decode(Value::Integer(i64::MAX), base64).await;
+            assert_eq!(
+                json_to_libsql_value(json!({ "$type": "int64", "value": json }), base64).unwrap(),
+                Value::Integer(i64::MAX)
+            );
+        });
+    }
+
+    #[test]
+    fn named_values_bind_with_any_prefix() {
+        block_on(async {
+            let conn = memory().await;
+            let bind = |values: JsonValue| {
+                let values = QueryValues::Named(values.as_object().unwrap().clone());
+                json_to_params(values, Encodings::default()).unwrap()
+            };
+            let first = |sql: &'static str, params| {
+                let conn = &conn;
+                async move {
+                    let mut rows = conn.query(sql, params).await.unwrap();
+                    rows.next().await.unwrap().unwrap().get_value(0).unwrap()
+                }
+            };
+
+            let params = bind(json!({ ":a": 1, "@b": 2, "$c": 3, "d": 4 }));
+            let mut rows = conn
+                .query("SELECT :a, @b, $c, :d, @d, $d", params)
+                .await
+                .unwrap();
+            let row = rows.next().await.unwrap().unwrap();
+            let bound: Vec<i64> = (0..6).map(|i| row.get(i).unwrap()).collect();
+            assert_eq!(bound, [1, 2, 3, 4, 4, 4]);
+
+            // A bare key binds a statement using any single prefix
+            for sql in ["SELECT $a", "SELECT @a", "SELECT :a"] {
+                assert_eq!(first(sql, bind(json!({ "a": 1 }))).await, Value::Integer(1));
+            }
+
+            // Like SQLite, keys the statement doesn't use are ignored, and
+            // parameters without a value bind NULL
+            assert_eq!(
+                first("SELECT :a", bind(json!({ "b": 1 }))).await,
+                Value::Null
+            );
+            assert_eq!(
+                first("SELECT :a", bind(json!({ "$a": 1 }))).await,
+                Value::Null
+            );
+        });
+    }
+}
@@ -1,4 +1,7 @@
-use serde::{ser::Serializer, Serialize};
+use serde::{
+    ser::{SerializeStruct, Serializer},
+    Serialize,
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -10,8 +13,51 @@ pub enum Error {
     Libsql(#[from] libsql::Error),
     #[error("invalid connection url: {0}")]
     InvalidDbUrl(String),
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
     #[error("database {0} not loaded")]
     DatabaseNotLoaded(String),
+    #[error("database {0} is still in use")]
+    DatabaseBusy(String),
+    #[error("transaction {0} not found (already committed, rolled back or timed out)")]
+    TransactionNotFound(u64),
+    #[error(
+        "statement {index} of the script (line {line}, byte offset {offset}) failed: {source}"
+    )]
+    ScriptStatement {
+        /// Zero-based position of the statement in the script
+        index: usize,
+        /// Byte offset of the statement in the script
+        offset: usize,
+        /// One-based line the statement starts on
+        line: usize,
+        source: Box<Error>,
+    },
+    #[error("migration {name} failed: {source}")]
+    Migration { name: String, source: Box<Error> },
+    #[error("reverting migration {name} failed: {source}")]
+    MigrationRevert { name: String, source: Box<Error> },
+    #[error("migration {0} has no down script and can't be reverted")]
+    MigrationIrreversible(String),
+    #[error(
+        "migration {name} has been modified since it was applied \
+         (recorded checksum {recorded}, now {current})"
+    )]
+    MigrationModified {
+        name: String,
+        recorded: String,
+        current: String,
+    },
+    #[error("invalid import: {0}")]
+    InvalidImport(String),
+    #[error("line {line} of the import file failed: {source}")]
+    ImportLine {
+        /// One-based line the record starts on
+        line: u64,
+        source: Box<Error>,
+    },
+    #[error("integrity check failed: {0}")]
+    IntegrityCheck(String),
     #[error("unsupported datatype: {0}")]
     UnsupportedDatatype(String),
     #[error("operation not supported: {0}")]
@@ -23,11 +69,28 @@ pub enum Error {
     PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
 }
 
+/// Errors reach the frontend as their message, except those it is expected to
+/// act on, which are objects with a `kind`, the `message` and their details.
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.serialize_str(self.to_string().as_ref())
+        match self {
+            Error::MigrationModified {
+                name,
+                recorded,
+                current,
+            } => {
+                let mut error = serializer.serialize_struct("Error", 5)?;
+                error.serialize_field("kind", "migrationModified")?;
+                error.serialize_field("message", &self.to_string())?;
+                error.serialize_field("name", name)?;
+                error.serialize_field("expected", recorded)?;
+                error.serialize_field("actual", current)?;
+                error.end()
+            }
+            _ => serializer.serialize_str(self.to_string().as_ref()),
+        }
     }
 }
@@ -0,0 +1,158 @@
+//! Writing query results to CSV or NDJSON, a row at a time.
+
+use indexmap::IndexMap;
+use libsql::Rows;
+use serde_json::Value as JsonValue;
+use std::io::Write;
+
+use crate::decode::Decoder;
+use crate::models::{CsvQuoting, ExportOptions, FileFormat};
+use crate::Error;
+
+/// Write every remaining row of `rows` to `out` as it is read. Returns the
+/// number of rows written.
+pub(crate) async fn write_rows(
+    mut rows: Rows,
+    decoder: &Decoder,
+    options: &ExportOptions,
+    out: &mut impl Write,
+) -> Result<u64, Error> {
+    let column_count = rows.column_count();
+    let columns: Vec<String> = (0..column_count)
+        .map(|i| rows.column_name(i).unwrap_or_default().to_string())
+        .collect();
+
+    if options.format == FileFormat::Csv && options.header {
+        write_csv_record(out, columns.iter().map(|c| Some(c.as_str())), options)?;
+    }
+
+    let mut count = 0;
+    let mut values = Vec::with_capacity(columns.len());
+    while let Some(row) = rows.next().await? {
+        values.clear();
+        for i in 0..column_count {
+            values.push(decoder.to_json(&row, i)?);
+        }
+        match options.format {
+            FileFormat::Csv => {
+                let cells: Vec<_> = values.iter().map(csv_cell).collect();
+                write_csv_record(out, cells.iter().map(|c| c.as_deref()), options)?;
+            }
+            FileFormat::Ndjson => {
+                let object: IndexMap<_, _> = columns.iter().zip(&values).collect();
+                serde_json::to_writer(&mut *out, &object)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        count += 1;
+    }
+    out.flush()?;
+    Ok(count)
+}
+
+/// Text of a CSV cell: strings as-is, everything else (numbers, blob arrays,
+/// tagged values) as JSON. `None` for NULL.
+fn csv_cell(value: &JsonValue) -> Option<std::borrow::Cow<'_, str>> {
+    match value {
+        JsonValue::Null => None,
+        JsonValue::String(s) => Some(s.as_str().into()),
+        other => Some(other.to_string().into()),
+    }
+}
+
+/// Write one CSV record, RFC 4180 style: fields containing the delimiter, a
+/// quote or a line break (or every field, with `CsvQuoting::All`) are quoted,
+/// with quotes doubled, and records end in CRLF. NULL is an empty unquoted
+/// field and an empty string `""`, so the two stay distinct.
+fn write_csv_record<'a>(
+    out: &mut impl Write,
+    fields: impl Iterator<Item = Option<&'a str>>,
+    options: &ExportOptions,
+) -> Result<(), Error> {
+    let mut delimiter = [0; 4];
+    let delimiter = options.delimiter.encode_utf8(&mut delimiter);
+
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.write_all(delimiter.as_bytes())?;
+        }
+        let Some(field) = field else {
+            continue;
+        };
+        let quote = options.quote == CsvQuoting::All
+            || field.is_empty()
+            || field.contains(['"', '\r', '\n'])
+            || field.contains(&*delimiter);
+        if quote {
+            write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            out.write_all(field.as_bytes())?;
+        }
+    }
+    out.write_all(b"\r\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Encodings;
+    use crate::models::BlobEncoding;
+    use crate::testing::{block_on, memory};
+    use libsql::params::Params;
+
+    async fn export(query: &str, options: ExportOptions) -> String {
+        let conn = memory().await;
+        let rows = conn.query(query, Params::None).await.unwrap();
+        let decoder = Decoder::new(Encodings {
+            blob: BlobEncoding::Base64,
+            ..Encodings::default()
+        })
+        .bare_blobs();
+        let mut out = Vec::new();
+        write_rows(rows, &decoder, &options, &mut out)
+            .await
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_csv_and_ndjson() {
+        block_on(async {
+            let query = "SELECT 1 AS id, 'a,\"b\"' AS name, NULL AS note, x'00ff' AS data \
+                         UNION ALL SELECT 9007199254740993, 'line' || char(10) || 'break', 1.5, NULL";
+
+            assert_eq!(
+                export(query, ExportOptions::default()).await,
+                "id,name,note,data\r\n\
+                 1,\"a,\"\"b\"\"\",,AP8=\r\n\
+                 9007199254740993,\"line\nbreak\",1.5,\r\n"
+            );
+            assert_eq!(
+                export(
+                    "SELECT 1 AS a, 'x' AS b, NULL AS c, '' AS d",
+                    ExportOptions {
+                        header: false,
+                        delimiter: ';',
+                        quote: CsvQuoting::All,
+                        ..ExportOptions::default()
+                    }
+                )
+                .await,
+                "\"1\";\"x\";;\"\"\r\n"
+            );
+            assert_eq!(
+                export(
+                    query,
+                    ExportOptions {
+                        format: FileFormat::Ndjson,
+                        ..ExportOptions::default()
+                    }
+                )
+                .await,
+                "{\"id\":1,\"name\":\"a,\\\"b\\\"\",\"note\":null,\"data\":\"AP8=\"}\n\
+                 {\"id\":9007199254740993,\"name\":\"line\\nbreak\",\"note\":1.5,\"data\":null}\n"
+            );
+        });
+    }
+}
@@ -0,0 +1,642 @@
+//! Bulk import of CSV or NDJSON files into a table.
+
+use indexmap::IndexMap;
+use libsql::{params::Params, Connection, Value};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::VecDeque;
+use std::io::BufRead;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::decode::Encodings;
+use crate::encode::json_to_libsql_value;
+use crate::models::{FileFormat, ImportEvent, ImportOptions, ImportResult};
+use crate::script::quote_identifier;
+use crate::Error;
+
+/// One record of the input file
+enum Record {
+    /// CSV fields; `None` for an empty unquoted field, read as NULL
+    Csv(Vec<Option<String>>),
+    Json(Map<String, JsonValue>),
+}
+
+/// A record and the line it starts on, or why it couldn't be read
+type NumberedRecord = (u64, Result<Record, Error>);
+
+/// Records read ahead of the inserts
+const READ_AHEAD: usize = 256;
+
+/// Reads records line by line, keeping track of the line number and the
+/// bytes consumed
+struct Reader<R> {
+    input: R,
+    format: FileFormat,
+    delimiter: char,
+    line: u64,
+    bytes_read: u64,
+    buf: String,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Read the next record and the line it starts on. Blank lines are
+    /// skipped. A malformed record is returned as an error in the inner
+    /// `Result` so it can be skipped; the outer one is for I/O errors.
+    fn next_record(&mut self) -> Result<Option<NumberedRecord>, Error> {
+        self.buf.clear();
+        loop {
+            if !self.read_line()? {
+                return Ok(None);
+            }
+            if !self.buf.trim().is_empty() {
+                break;
+            }
+            self.buf.clear();
+        }
+        let line = self.line;
+
+        let record = match self.format {
+            FileFormat::Csv => {
+                // A quoted field may span lines; an odd number of quotes
+                // means one is still open
+                while self.buf.matches('"').count() % 2 == 1 && self.read_line()? {}
+                let text = self.buf.trim_end_matches(['\r', '\n']);
+                let text = match line {
+                    1 => text.strip_prefix('\u{feff}').unwrap_or(text),
+                    _ => text,
+                };
+                parse_csv_record(text, self.delimiter)
+                    .map(Record::Csv)
+                    .map_err(Error::InvalidImport)
+            }
+            FileFormat::Ndjson => match serde_json::from_str(&self.buf) {
+                Ok(JsonValue::Object(map)) => Ok(Record::Json(map)),
+                Ok(_) => Err(Error::InvalidImport("expected a JSON object".into())),
+                Err(e) => Err(e.into()),
+            },
+        };
+        Ok(Some((line, record)))
+    }
+
+    /// Append the next line to the buffer; `false` at the end of the input
+    fn read_line(&mut self) -> Result<bool, Error> {
+        let n = self.input.read_line(&mut self.buf)?;
+        self.bytes_read += n as u64;
+        self.line += u64::from(n > 0);
+        Ok(n > 0)
+    }
+}
+
+/// Records read by a `Reader` on the blocking thread pool, so file I/O and
+/// parsing don't hold up the async runtime
+struct Records {
+    /// Each record with the bytes of input consumed once it was read; an I/O
+    /// error ends the input
+    rx: mpsc::Receiver<Result<(NumberedRecord, u64), Error>>,
+    reading: Option<JoinHandle<()>>,
+    bytes_read: u64,
+}
+
+impl Records {
+    fn spawn<R: BufRead + Send + 'static>(mut reader: Reader<R>) -> Self {
+        let (tx, rx) = mpsc::channel(READ_AHEAD);
+        let reading = tokio::task::spawn_blocking(move || loop {
+            let next = match reader.next_record() {
+                Ok(Some(record)) => Ok((record, reader.bytes_read)),
+                Ok(None) => break,
+                Err(e) => Err(e),
+            };
+            let failed = next.is_err();
+            // The receiver is gone once the import has stopped
+            if tx.blocking_send(next).is_err() || failed {
+                break;
+            }
+        });
+        Self {
+            rx,
+            reading: Some(reading),
+            bytes_read: 0,
+        }
+    }
+
+    /// The next record, as `Reader::next_record` returns it
+    async fn next(&mut self) -> Result<Option<NumberedRecord>, Error> {
+        match self.rx.recv().await {
+            Some(Ok((record, bytes_read))) => {
+                self.bytes_read = bytes_read;
+                Ok(Some(record))
+            }
+            Some(Err(e)) => Err(e),
+            None => {
+                // A reader that panicked must not pass for the end of input
+                if let Some(reading) = self.reading.take() {
+                    reading.await.map_err(std::io::Error::other)?;
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Split one CSV record, without its line terminator, into fields. Quoted
+/// fields may contain the delimiter, line breaks and doubled quotes.
+fn parse_csv_record(text: &str, delimiter: char) -> Result<Vec<Option<String>>, String> {
+    let mut fields = Vec::new();
+    let mut chars = text.chars().peekable();
+    loop {
+        let mut field = String::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next() {
+                    Some('"') if chars.next_if_eq(&'"').is_some() => field.push('"'),
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err("unterminated quoted field".into()),
+                }
+            }
+            fields.push(Some(field));
+            match chars.next() {
+                None => return Ok(fields),
+                Some(c) if c == delimiter => {}
+                Some(c) => return Err(format!("unexpected {c:?} after a quoted field")),
+            }
+        } else {
+            let mut end = true;
+            for c in chars.by_ref() {
+                if c == delimiter {
+                    end = false;
+                    break;
+                }
+                field.push(c);
+            }
+            fields.push((!field.is_empty()).then_some(field));
+            if end {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+/// Import every record of `input` into `table` in one transaction, sending
+/// progress and, with `skip_errors`, failed records to `send`. `input` is
+/// read on the blocking thread pool. `total_bytes` is only passed through to
+/// the progress events.
+pub(crate) async fn import(
+    conn: &Connection,
+    input: impl BufRead + Send + 'static,
+    total_bytes: u64,
+    table: &str,
+    options: &ImportOptions,
+    encodings: Encodings,
+    mut send: impl FnMut(ImportEvent),
+) -> Result<ImportResult, Error> {
+    let mut records = Records::spawn(Reader {
+        input,
+        format: options.format,
+        delimiter: options.delimiter,
+        line: 0,
+        bytes_read: 0,
+        buf: String::new(),
+    });
+
+    // Field names: the CSV header, or the keys of the first well-formed
+    // NDJSON object when there's no `columns` mapping. Records read to find
+    // them are then imported first, malformed ones skipped with `skip_errors`.
+    let mut pending = VecDeque::new();
+    let fields: Option<Vec<String>> = match options.format {
+        FileFormat::Csv if options.header => match records.next().await? {
+            Some((_, Ok(Record::Csv(header)))) => {
+                Some(header.into_iter().map(Option::unwrap_or_default).collect())
+            }
+            Some((line, Err(e))) => return Err(line_error(line, e)),
+            _ => return import_empty(conn, table, options).await,
+        },
+        FileFormat::Ndjson if options.columns.is_none() => loop {
+            match records.next().await? {
+                Some((line, Ok(Record::Json(object)))) => {
+                    let keys = object.keys().cloned().collect();
+                    pending.push_back((line, Ok(Record::Json(object))));
+                    break Some(keys);
+                }
+                Some((line, Err(e))) if options.skip_errors => pending.push_back((line, Err(e))),
+                Some((line, Err(e))) => return Err(line_error(line, e)),
+                _ => {
+                    let mut result = import_empty(conn, table, options).await?;
+                    for (line, record) in pending {
+                        if let Err(e) = record {
+                            result.rows_failed += 1;
+                            send(ImportEvent::LineError {
+                                line,
+                                message: e.to_string(),
+                            });
+                        }
+                    }
+                    return Ok(result);
+                }
+            }
+        },
+        _ => None,
+    };
+
+    let mapping: IndexMap<String, String> = match (&options.columns, &fields) {
+        (Some(columns), _) => columns.clone(),
+        (None, Some(fields)) => fields.iter().map(|f| (f.clone(), f.clone())).collect(),
+        (None, None) => {
+            return Err(Error::InvalidImport(
+                "`columns` is required for a CSV file without a header".into(),
+            ))
+        }
+    };
+    if mapping.is_empty() {
+        return Err(Error::InvalidImport("no columns to import".into()));
+    }
+
+    // Position of each mapped CSV field: by name in the header, otherwise
+    // the field names are one-based positions
+    let positions = match options.format {
+        FileFormat::Csv => mapping
+            .keys()
+            .map(|field| {
+                let position = match &fields {
+                    Some(header) => header.iter().position(|f| f == field),
+                    None => field.parse::<usize>().ok().and_then(|n| n.checked_sub(1)),
+                };
+                position
+                    .ok_or_else(|| Error::InvalidImport(format!("no field {field:?} in the file")))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        FileFormat::Ndjson => Vec::new(),
+    };
+    let field_count = fields.as_ref().map(Vec::len);
+
+    let table = quote_identifier(table);
+    let columns: Vec<_> = mapping.values().map(|c| quote_identifier(c)).collect();
+    let columns = columns.join(", ");
+
+    conn.execute("BEGIN", Params::None).await?;
+    let result = async {
+        if options.create_table {
+            conn.execute(
+                &format!("CREATE TABLE IF NOT EXISTS {table} ({columns})"),
+                Params::None,
+            )
+            .await?;
+        }
+        let placeholders: Vec<_> = (1..=mapping.len()).map(|i| format!("?{i}")).collect();
+        let insert = conn
+            .prepare(&format!(
+                "INSERT INTO {table} ({columns}) VALUES ({})",
+                placeholders.join(", ")
+            ))
+            .await?;
+
+        let mut result = ImportResult::default();
+        let mut field_count = field_count;
+        let progress_interval = options.progress_interval.max(1);
+        let progress = |records: &Records, result: &ImportResult| ImportEvent::Progress {
+            rows_imported: result.rows_imported,
+            rows_failed: result.rows_failed,
+            bytes_read: records.bytes_read,
+            total_bytes,
+        };
+
+        loop {
+            let (line, record) = match pending.pop_front() {
+                Some(pending) => pending,
+                None => match records.next().await? {
+                    Some(next) => next,
+                    None => break,
+                },
+            };
+
+            let values = record.and_then(|record| match record {
+                Record::Csv(mut cells) => {
+                    let expected = *field_count.get_or_insert(cells.len());
+                    if cells.len() != expected {
+                        return Err(Error::InvalidImport(format!(
+                            "expected {expected} fields, found {}",
+                            cells.len()
+                        )));
+                    }
+                    positions
+                        .iter()
+                        .map(|&i| match cells.get_mut(i) {
+                            Some(cell) => Ok(cell.take().map_or(Value::Null, Value::Text)),
+                            None => Err(Error::InvalidImport(format!(
+                                "no field {} in a record of {expected}",
+                                i + 1
+                            ))),
+                        })
+                        .collect()
+                }
+                Record::Json(mut object) => mapping
+                    .keys()
+                    .map(|field| {
+                        let value = object.remove(field).unwrap_or(JsonValue::Null);
+                        json_to_libsql_value(value, encodings)
+                    })
+                    .collect(),
+            });
+            let inserted = match values {
+                Ok(values) => {
+                    let inserted = insert.execute(Params::Positional(values)).await;
+                    insert.reset();
+                    inserted.map(|_| ()).map_err(Error::from)
+                }
+                Err(e) => Err(e),
+            };
+
+            match inserted {
+                Ok(()) => result.rows_imported += 1,
+                Err(e) if options.skip_errors => {
+                    result.rows_failed += 1;
+                    send(ImportEvent::LineError {
+                        line,
+                        message: e.to_string(),
+                    });
+                }
+                Err(e) => return Err(line_error(line, e)),
+            }
+            if (result.rows_imported + result.rows_failed) % progress_interval == 0 {
+                send(progress(&records, &result));
+            }
+        }
+
+        // Unless it was just sent for the last record
+        let count = result.rows_imported + result.rows_failed;
+        if count == 0 || count % progress_interval != 0 {
+            send(progress(&records, &result));
+        }
+        Ok(result)
+    }
+    .await;
+
+    match result {
+        Ok(result) => {
+            if let Err(e) = conn.execute("COMMIT", Params::None).await {
+                // A failed COMMIT (e.g. SQLITE_BUSY) can leave the transaction open
+                if !conn.is_autocommit() {
+                    let _ = conn.execute("ROLLBACK", Params::None).await;
+                }
+                return Err(e.into());
+            }
+            Ok(result)
+        }
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", Params::None).await;
+            Err(e)
+        }
+    }
+}
+
+/// An empty file imports nothing, but still creates the table when asked to,
+/// which needs `columns` since there is no header or object to name them.
+async fn import_empty(
+    conn: &Connection,
+    table: &str,
+    options: &ImportOptions,
+) -> Result<ImportResult, Error> {
+    if options.create_table {
+        let Some(columns) = &options.columns else {
+            return Err(Error::InvalidImport(
+                "`columns` is required to create a table from an empty file".into(),
+            ));
+        };
+        let columns: Vec<_> = columns.values().map(|c| quote_identifier(c)).collect();
+        conn.execute(
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {} ({})",
+                quote_identifier(table),
+                columns.join(", ")
+            ),
+            Params::None,
+        )
+        .await?;
+    }
+    Ok(ImportResult::default())
+}
+
+fn line_error(line: u64, source: Error) -> Error {
+    Error::ImportLine {
+        line,
+        source: Box::new(source),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, memory};
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_quoted_csv_fields() {
+        assert_eq!(
+            parse_csv_record("1,\"a,\"\"b\"\"\",,\"\"", ','),
+            Ok(vec![
+                Some("1".into()),
+                Some("a,\"b\"".into()),
+                None,
+                Some("".into())
+            ])
+        );
+        assert!(parse_csv_record("\"a\"b", ',').is_err());
+    }
+
+    #[test]
+    fn imports_csv_and_ndjson() {
+        block_on(async {
+            let conn = memory().await;
+            conn.execute("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT)", ())
+                .await
+                .unwrap();
+
+            let csv = "\u{feff}ID,Name\r\n1,\"multi\nline\"\r\n\r\nx,bad\r\n2,\r\n";
+            let mut events = Vec::new();
+            let result = import(
+                &conn,
+                Cursor::new(csv),
+                csv.len() as u64,
+                "t",
+                &ImportOptions {
+                    columns: Some(IndexMap::from([
+                        ("ID".into(), "id".into()),
+                        ("Name".into(), "name".into()),
+                    ])),
+                    skip_errors: true,
+                    ..ImportOptions::default()
+                },
+                Encodings::default(),
+                |event| events.push(event),
+            )
+            .await
+            .unwrap();
+            assert_eq!((result.rows_imported, result.rows_failed), (2, 1));
+            assert!(matches!(events[0], ImportEvent::LineError { line: 5, .. }));
+            assert!(matches!(
+                events[1],
+                ImportEvent::Progress { bytes_read, total_bytes, .. } if bytes_read == total_bytes
+            ));
+
+            let ndjson = "{\"id\": 3, \"name\": \"c\"}\n{\"id\": 3}\n";
+            let err = import(
+                &conn,
+                Cursor::new(ndjson),
+                0,
+                "t",
+                &ImportOptions {
+                    format: FileFormat::Ndjson,
+                    ..ImportOptions::default()
+                },
+                Encodings::default(),
+                |_| {},
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, Error::ImportLine { line: 2, .. }));
+
+            let mut rows = conn
+                .query("SELECT id, name FROM t ORDER BY id", ())
+                .await
+                .unwrap();
+            let mut names = Vec::new();
+            while let Some(row) = rows.next().await.unwrap() {
+                names.push((
+                    row.get::<i64>(0).unwrap(),
+                    row.get::<Option<String>>(1).unwrap(),
+                ));
+            }
+            assert_eq!(names, [(1, Some("multi\nline".into())), (2, None)]);
+        });
+    }
+
+    #[test]
+    fn skips_malformed_records_before_the_first_ndjson_object() {
+        block_on(async {
+            let conn = memory().await;
+
+            let ndjson = "{\"id\": 1,\n[2]\n{\"id\": 3, \"name\": \"c\"}\n{\"id\": 4}\n";
+            let mut lines = Vec::new();
+            let result = import(
+                &conn,
+                Cursor::new(ndjson),
+                0,
+                "t",
+                &ImportOptions {
+                    format: FileFormat::Ndjson,
+                    create_table: true,
+                    skip_errors: true,
+                    ..ImportOptions::default()
+                },
+                Encodings::default(),
+                |event| {
+                    if let ImportEvent::LineError { line, .. } = event {
+                        lines.push(line);
+                    }
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!((result.rows_imported, result.rows_failed), (2, 2));
+            assert_eq!(lines, [1, 2]);
+
+            let mut rows = conn
+                .query("SELECT id, name FROM t ORDER BY id", ())
+                .await
+                .unwrap();
+            let mut imported = Vec::new();
+            while let Some(row) = rows.next().await.unwrap() {
+                imported.push((
+                    row.get::<i64>(0).unwrap(),
+                    row.get::<Option<String>>(1).unwrap(),
+                ));
+            }
+            assert_eq!(imported, [(3, Some("c".into())), (4, None)]);
+
+            // Without `skip_errors` the first malformed record still fails
+            let err = import(
+                &conn,
+                Cursor::new(ndjson),
+                0,
+                "t",
+                &ImportOptions {
+                    format: FileFormat::Ndjson,
+                    ..ImportOptions::default()
+                },
+                Encodings::default(),
+                |_| {},
+            )
+            .await
+            .unwrap_err();
+            assert!(matches!(err, Error::ImportLine { line: 1, .. }));
+        });
+    }
+
+    #[test]
+    fn reports_progress_once_per_interval_and_creates_tables_for_empty_files() {
+        block_on(async {
+            let conn = memory().await;
+
+            let csv = "x\n1\n2\n3\n4\n";
+            let mut progress = Vec::new();
+            import(
+                &conn,
+                Cursor::new(csv),
+                csv.len() as u64,
+                "t",
+                &ImportOptions {
+                    create_table: true,
+                    progress_interval: 2,
+                    ..ImportOptions::default()
+                },
+                Encodings::default(),
+                |event| {
+                    if let ImportEvent::Progress { rows_imported, .. } = event {
+                        progress.push(rows_imported);
+                    }
+                },
+            )
+            .await
+            .unwrap();
+            assert_eq!(progress, [2, 4]);
+
+            let columns = IndexMap::from([("a".to_string(), "a".to_string())]);
+            for format in [FileFormat::Csv, FileFormat::Ndjson] {
+                let create = |columns| ImportOptions {
+                    format,
+                    columns,
+                    create_table: true,
+                    ..ImportOptions::default()
+                };
+                let empty = || Cursor::new("");
+                let err = import(
+                    &conn,
+                    empty(),
+                    0,
+                    "e",
+                    &create(None),
+                    Encodings::default(),
+                    |_| {},
+                )
+                .await
+                .unwrap_err();
+                assert!(matches!(err, Error::InvalidImport(_)));
+
+                let options = create(Some(columns.clone()));
+                let result = import(
+                    &conn,
+                    empty(),
+                    0,
+                    "e",
+                    &options,
+                    Encodings::default(),
+                    |_| {},
+                )
+                .await
+                .unwrap();
+                assert_eq!(result.rows_imported, 0);
+                conn.query("SELECT a FROM e", ()).await.unwrap();
+            }
+        });
+    }
+}
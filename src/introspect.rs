@@ -0,0 +1,282 @@
+//! Structured description of a database's schema, read from `sqlite_master`
+//! and the `pragma_*` table-valued functions.
+
+use libsql::{params::IntoParams, Connection, Row};
+
+use crate::models::{
+    ColumnInfo, ForeignKeyInfo, IndexInfo, SchemaInfo, TableInfo, TriggerInfo, ViewInfo,
+};
+use crate::script::quote_identifier;
+use crate::Error;
+
+/// Describe every schema of `conn` (`main`, attached databases, and `temp`
+/// once it's in use), in `PRAGMA database_list` order. SQLite's internal `sqlite_*`
+/// objects are left out.
+pub(crate) async fn introspect(conn: &Connection) -> Result<Vec<SchemaInfo>, Error> {
+    let databases = query_all(
+        conn,
+        "SELECT name, file FROM pragma_database_list",
+        (),
+        |row| Ok((row.get::<String>(0)?, row.get::<Option<String>>(1)?)),
+    )
+    .await?;
+
+    let mut schemas = Vec::with_capacity(databases.len());
+    for (name, file) in databases {
+        schemas.push(describe_schema(conn, name, file).await?);
+    }
+    Ok(schemas)
+}
+
+async fn describe_schema(
+    conn: &Connection,
+    schema: String,
+    file: Option<String>,
+) -> Result<SchemaInfo, Error> {
+    let objects = query_all(
+        conn,
+        &format!(
+            "SELECT type, name, tbl_name, sql FROM {}.sqlite_master
+             WHERE type IN ('table', 'view', 'trigger') AND name NOT LIKE 'sqlite\\_%' ESCAPE '\\'
+             ORDER BY name",
+            quote_identifier(&schema)
+        ),
+        (),
+        |row| {
+            Ok((
+                row.get::<String>(0)?,
+                row.get::<String>(1)?,
+                row.get::<String>(2)?,
+                row.get::<Option<String>>(3)?,
+            ))
+        },
+    )
+    .await?;
+
+    let mut info = SchemaInfo {
+        name: schema,
+        file: file.filter(|f| !f.is_empty()),
+        tables: Vec::new(),
+        views: Vec::new(),
+        triggers: Vec::new(),
+    };
+    for (object_type, name, table, sql) in objects {
+        match object_type.as_str() {
+            "table" => {
+                let table = TableInfo {
+                    columns: columns(conn, &info.name, &name).await?,
+                    indexes: indexes(conn, &info.name, &name).await?,
+                    foreign_keys: foreign_keys(conn, &info.name, &name).await?,
+                    name,
+                    sql,
+                };
+                info.tables.push(table);
+            }
+            "view" => {
+                let view = ViewInfo {
+                    columns: columns(conn, &info.name, &name).await?,
+                    name,
+                    sql,
+                };
+                info.views.push(view);
+            }
+            _ => info.triggers.push(TriggerInfo { name, table, sql }),
+        }
+    }
+    Ok(info)
+}
+
+async fn columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<ColumnInfo>, Error> {
+    // `table_xinfo` also lists generated columns; hidden columns of virtual
+    // tables (hidden = 1) aren't part of the table's shape
+    query_all(
+        conn,
+        "SELECT name, type, \"notnull\", dflt_value, pk, hidden
+         FROM pragma_table_xinfo(?1, ?2) WHERE hidden != 1 ORDER BY cid",
+        [table, schema],
+        |row| {
+            let decl_type = row.get::<String>(1)?;
+            let primary_key = row.get::<u32>(4)?;
+            Ok(ColumnInfo {
+                name: row.get(0)?,
+                decl_type: (!decl_type.is_empty()).then_some(decl_type),
+                not_null: row.get::<i64>(2)? != 0,
+                default_value: row.get(3)?,
+                primary_key: (primary_key > 0).then_some(primary_key),
+                generated: row.get::<i64>(5)? > 1,
+            })
+        },
+    )
+    .await
+}
+
+async fn indexes(conn: &Connection, schema: &str, table: &str) -> Result<Vec<IndexInfo>, Error> {
+    // `sql` is NULL for indexes SQLite creates for UNIQUE and PRIMARY KEY
+    let list = query_all(
+        conn,
+        &format!(
+            "SELECT l.name, l.\"unique\", l.origin, l.partial, m.sql
+             FROM pragma_index_list(?1, ?2) AS l
+             LEFT JOIN {}.sqlite_master AS m ON m.type = 'index' AND m.name = l.name
+             ORDER BY l.seq",
+            quote_identifier(schema)
+        ),
+        [table, schema],
+        |row| {
+            Ok((
+                row.get::<String>(0)?,
+                row.get::<i64>(1)? != 0,
+                row.get::<String>(2)?,
+                row.get::<i64>(3)? != 0,
+                row.get::<Option<String>>(4)?,
+            ))
+        },
+    )
+    .await?;
+
+    let mut indexes = Vec::with_capacity(list.len());
+    for (name, unique, origin, partial, sql) in list {
+        // `name` is NULL for expression columns
+        let columns = query_all(
+            conn,
+            "SELECT name FROM pragma_index_info(?1, ?2) ORDER BY seqno",
+            [name.as_str(), schema],
+            |row| Ok(row.get::<Option<String>>(0)?),
+        )
+        .await?;
+        indexes.push(IndexInfo {
+            name,
+            unique,
+            origin,
+            partial,
+            columns,
+            sql,
+        });
+    }
+    Ok(indexes)
+}
+
+async fn foreign_keys(
+    conn: &Connection,
+    schema: &str,
+    table: &str,
+) -> Result<Vec<ForeignKeyInfo>, Error> {
+    let rows = query_all(
+        conn,
+        "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete
+         FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+        [table, schema],
+        |row| {
+            Ok((
+                row.get::<i64>(0)?,
+                row.get::<String>(1)?,
+                row.get::<String>(2)?,
+                row.get::<Option<String>>(3)?,
+                row.get::<String>(4)?,
+                row.get::<String>(5)?,
+            ))
+        },
+    )
+    .await?;
+
+    // One row per column; composite keys share an id
+    let mut keys: Vec<(i64, ForeignKeyInfo)> = Vec::new();
+    for (id, table, from, to, on_update, on_delete) in rows {
+        match keys.last_mut() {
+            Some((last, key)) if *last == id => {
+                key.columns.push(from);
+                key.references.push(to);
+            }
+            _ => keys.push((
+                id,
+                ForeignKeyInfo {
+                    table,
+                    columns: vec![from],
+                    references: vec![to],
+                    on_update,
+                    on_delete,
+                },
+            )),
+        }
+    }
+    Ok(keys.into_iter().map(|(_, key)| key).collect())
+}
+
+/// Run `sql` and map every row with `map`
+async fn query_all<T>(
+    conn: &Connection,
+    sql: &str,
+    params: impl IntoParams,
+    map: impl Fn(&Row) -> Result<T, Error>,
+) -> Result<Vec<T>, Error> {
+    let mut rows = conn.query(sql, params).await?;
+    let mut out = Vec::new();
+    while let Some(row) = rows.next().await? {
+        out.push(map(&row)?);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, memory};
+
+    #[test]
+    fn describes_tables_indexes_and_attached_schemas() {
+        block_on(async {
+            let conn = memory().await;
+            conn.execute_batch(
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, email TEXT NOT NULL UNIQUE);
+                 CREATE TABLE posts (
+                     id INTEGER PRIMARY KEY,
+                     user_id INTEGER REFERENCES users (id) ON DELETE CASCADE,
+                     title TEXT DEFAULT 'untitled',
+                     slug TEXT GENERATED ALWAYS AS (lower(title))
+                 );
+                 CREATE INDEX posts_user ON posts (user_id);
+                 CREATE VIEW titles AS SELECT title FROM posts;
+                 CREATE TRIGGER posts_touch AFTER UPDATE ON posts BEGIN SELECT 1; END;
+                 ATTACH ':memory:' AS extra;
+                 CREATE TABLE extra.notes (body TEXT);",
+            )
+            .await
+            .unwrap();
+
+            let schemas = introspect(&conn).await.unwrap();
+            let names: Vec<_> = schemas.iter().map(|s| s.name.as_str()).collect();
+            assert_eq!(names, ["main", "extra"]);
+            let main = &schemas[0];
+
+            let posts = main.tables.iter().find(|t| t.name == "posts").unwrap();
+            let columns: Vec<_> = posts
+                .columns
+                .iter()
+                .map(|c| (c.name.as_str(), c.primary_key, c.generated))
+                .collect();
+            assert_eq!(
+                columns,
+                [
+                    ("id", Some(1), false),
+                    ("user_id", None, false),
+                    ("title", None, false),
+                    ("slug", None, true),
+                ]
+            );
+            assert_eq!(
+                posts.columns[2].default_value.as_deref(),
+                Some("'untitled'")
+            );
+            assert_eq!(posts.indexes[0].columns, [Some("user_id".to_string())]);
+            assert_eq!(posts.foreign_keys[0].table, "users");
+            assert_eq!(posts.foreign_keys[0].on_delete, "CASCADE");
+
+            let users = main.tables.iter().find(|t| t.name == "users").unwrap();
+            assert!(users.indexes[0].unique && users.indexes[0].sql.is_none());
+
+            assert_eq!(main.views[0].columns[0].name, "title");
+            assert_eq!(main.triggers[0].table, "posts");
+            assert_eq!(schemas[1].tables[0].name, "notes");
+        });
+    }
+}
@@ -10,13 +10,25 @@ mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+mod backup;
 mod commands;
 mod decode;
+mod dump;
+mod encode;
 mod error;
+mod export;
+mod import;
+mod introspect;
+mod migrate;
 mod models;
+mod msgpack;
+mod script;
+#[cfg(test)]
+mod testing;
 mod wrapper;
 
 pub use error::{Error, Result};
+pub use migrate::{Migration, MIGRATIONS_TABLE, TARGET_TABLE};
 pub use wrapper::DbInstances;
 
 /// Re-export Config for convenience
@@ -33,9 +45,29 @@ pub fn init_with_config<R: Runtime>(config: Config) -> TauriPlugin<R> {
             commands::load,
             commands::execute,
             commands::batch,
+            commands::batch_params,
+            commands::execute_script,
+            commands::query,
             commands::select,
+            commands::select_binary,
+            commands::select_stream,
+            commands::ack_stream,
+            commands::cancel_stream,
+            commands::begin_transaction,
+            commands::commit,
+            commands::rollback,
             commands::sync,
             commands::close,
+            commands::export_sql,
+            commands::export_query,
+            commands::import_file,
+            commands::backup,
+            commands::restore,
+            commands::import_sql,
+            commands::introspect,
+            commands::migrate_to,
+            commands::migration_plan,
+            commands::migration_status,
             commands::ping,
             commands::get_config
         ])
@@ -0,0 +1,780 @@
+//! Migrations configured in Rust and applied when a database is loaded.
+//!
+//! Applied migrations are recorded by name in the same `__drizzle_migrations`
+//! table the TypeScript `migrate()` uses, so a database can move between the
+//! two without re-running anything. Migrations applied from Rust also record a
+//! checksum of their SQL, so one edited after it ran is reported instead of
+//! silently skipped.
+//!
+//! Migrations with a `down` script can be reverted with `migrate_to`, and
+//! `plan` dry-runs either direction. A version below the newest migration is
+//! remembered in the database, so reopening it doesn't undo the rollback.
+
+use libsql::{params::Params, Connection, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::{
+    MigrateToResult, MigrationDirection, MigrationPlan, MigrationState, MigrationStatus,
+    PlannedMigration, SchemaChange, SchemaChangeKind,
+};
+use crate::script::split_statements;
+use crate::wrapper::run_script;
+use crate::Error;
+
+/// Ledger of applied migrations, shared with the TypeScript `migrate()`
+pub const MIGRATIONS_TABLE: &str = "__drizzle_migrations";
+
+/// The version `migrate_to` last moved the database to, while it is below the
+/// newest configured migration. `apply` doesn't go past it.
+pub const TARGET_TABLE: &str = "__libsql_migration_target";
+
+/// A versioned SQL migration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    /// Position in the migration order; migrations run lowest first
+    pub version: u64,
+    /// Id recorded in the ledger once applied, e.g. `0001_add_posts.sql`
+    pub name: String,
+    /// SQL script; may hold several statements
+    pub sql: String,
+    /// Script that undoes `sql`, for `migrate_to` an earlier version
+    pub down: Option<String>,
+}
+
+impl Migration {
+    pub fn new(version: u64, name: impl Into<String>, sql: impl Into<String>) -> Self {
+        Self {
+            version,
+            name: name.into(),
+            sql: sql.into(),
+            down: None,
+        }
+    }
+
+    /// Set the script that reverts this migration
+    pub fn with_down(mut self, sql: impl Into<String>) -> Self {
+        self.down = Some(sql.into());
+        self
+    }
+
+    /// Build migrations from `(path, sql)` pairs, e.g. drizzle-kit output
+    /// embedded with `include_str!`. Names and versions follow the TypeScript
+    /// `migrate()`: a flat `0003_add_posts.sql` is version 3 named after the
+    /// file, and a folder migration `0003_add_posts/migration.sql` is named
+    /// `0003_add_posts.sql`. Paths without a numeric prefix are skipped.
+    ///
+    /// Down scripts are paired with the migration of the same version: name
+    /// them `0003_add_posts.down.sql`, or `down.sql` inside the folder.
+    pub fn from_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Vec<Self> {
+        let mut migrations = Vec::new();
+        let mut downs = HashMap::new();
+        for (path, sql) in files {
+            match parse_path(path) {
+                Some((version, _, true)) => {
+                    downs.insert(version, sql);
+                }
+                Some((version, name, false)) => migrations.push(Self::new(version, name, sql)),
+                None => {}
+            }
+        }
+        for migration in &mut migrations {
+            migration.down = downs.get(&migration.version).map(|sql| sql.to_string());
+        }
+        migrations.sort_by_key(|m| m.version);
+        migrations
+    }
+}
+
+/// Version and ledger name of a migration file path, and whether it's a down
+/// script
+fn parse_path(path: &str) -> Option<(u64, String, bool)> {
+    let mut segments = path.rsplit(['/', '\\']).filter(|s| !s.is_empty());
+    let file = segments.next()?;
+
+    if file == "migration.sql" || file == "down.sql" {
+        let folder = segments.next()?;
+        return numeric_prefix(folder)
+            .map(|version| (version, format!("{folder}.sql"), file == "down.sql"));
+    }
+    let stem = file.strip_suffix(".sql")?;
+    let (stem, down) = match stem.strip_suffix(".down") {
+        Some(stem) => (stem, true),
+        None => (stem, false),
+    };
+    numeric_prefix(stem).map(|version| (version, format!("{stem}.sql"), down))
+}
+
+/// The leading number of `0003_name` or `0003-name`
+fn numeric_prefix(name: &str) -> Option<u64> {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match name[digits..].chars().next() {
+        Some('_' | '-') if digits > 0 => name[..digits].parse().ok(),
+        _ => None,
+    }
+}
+
+/// SHA-256 of a migration's SQL, hex-encoded, as recorded in the ledger
+pub fn checksum(sql: &str) -> String {
+    Sha256::digest(sql.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// A row of the ledger
+struct Applied {
+    /// `None` for rows written by the TypeScript `migrate()`, or before
+    /// checksums were recorded
+    checksum: Option<String>,
+    applied_at: Option<String>,
+}
+
+/// Create the ledger, or add the `checksum` column to one created without it
+async fn ensure_ledger(conn: &Connection) -> Result<(), Error> {
+    conn.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {MIGRATIONS_TABLE} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                hash TEXT NOT NULL UNIQUE,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                checksum TEXT
+            )"
+        ),
+        Params::None,
+    )
+    .await?;
+    if !ledger_has_checksums(conn).await? {
+        conn.execute(
+            &format!("ALTER TABLE {MIGRATIONS_TABLE} ADD COLUMN checksum TEXT"),
+            Params::None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+async fn ledger_has_checksums(conn: &Connection) -> Result<bool, Error> {
+    let mut rows = conn
+        .query(
+            "SELECT 1 FROM pragma_table_info(?1) WHERE name = 'checksum'",
+            [MIGRATIONS_TABLE],
+        )
+        .await?;
+    Ok(rows.next().await?.is_some())
+}
+
+/// Read the ledger, keyed by migration name. Doesn't write, so an empty map is
+/// returned when the ledger doesn't exist yet.
+async fn read_ledger(conn: &Connection) -> Result<HashMap<String, Applied>, Error> {
+    let mut rows = conn
+        .query(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [MIGRATIONS_TABLE],
+        )
+        .await?;
+    if rows.next().await?.is_none() {
+        return Ok(HashMap::new());
+    }
+
+    let checksum = if ledger_has_checksums(conn).await? {
+        "checksum"
+    } else {
+        "NULL"
+    };
+    let mut rows = conn
+        .query(
+            &format!("SELECT hash, {checksum}, created_at FROM {MIGRATIONS_TABLE}"),
+            Params::None,
+        )
+        .await?;
+    let mut ledger = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        // drizzle-kit's own migrator stores `created_at` as epoch milliseconds
+        let applied_at = match row.get_value(2)? {
+            Value::Text(s) => Some(s),
+            Value::Integer(i) => Some(i.to_string()),
+            _ => None,
+        };
+        ledger.insert(
+            row.get::<String>(0)?,
+            Applied {
+                checksum: row.get::<Option<String>>(1)?,
+                applied_at,
+            },
+        );
+    }
+    Ok(ledger)
+}
+
+/// The version `migrate_to` pinned the database at, if any. Doesn't write.
+async fn read_target(conn: &Connection) -> Result<Option<u64>, Error> {
+    let mut rows = conn
+        .query(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            [TARGET_TABLE],
+        )
+        .await?;
+    if rows.next().await?.is_none() {
+        return Ok(None);
+    }
+    let mut rows = conn
+        .query(&format!("SELECT version FROM {TARGET_TABLE}"), Params::None)
+        .await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get::<u64>(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// Pin the database at `target`, or unpin it with `None`
+async fn write_target(conn: &Connection, target: Option<u64>) -> Result<(), Error> {
+    let Some(target) = target else {
+        conn.execute(
+            &format!("DROP TABLE IF EXISTS {TARGET_TABLE}"),
+            Params::None,
+        )
+        .await?;
+        return Ok(());
+    };
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {TARGET_TABLE} (version INTEGER NOT NULL)"),
+        Params::None,
+    )
+    .await?;
+    conn.execute(&format!("DELETE FROM {TARGET_TABLE}"), Params::None)
+        .await?;
+    conn.execute(
+        &format!("INSERT INTO {TARGET_TABLE} (version) VALUES (?1)"),
+        [target],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Fail if an applied migration's SQL no longer matches its recorded checksum
+fn check_drift(migrations: &[Migration], ledger: &HashMap<String, Applied>) -> Result<(), Error> {
+    for migration in migrations {
+        let Some(recorded) = ledger
+            .get(&migration.name)
+            .and_then(|a| a.checksum.as_ref())
+        else {
+            continue;
+        };
+        let current = checksum(&migration.sql);
+        if *recorded != current {
+            return Err(Error::MigrationModified {
+                name: migration.name.clone(),
+                recorded: recorded.clone(),
+                current,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Apply the migrations that aren't in the ledger yet, in version order, up
+/// to the version `migrate_to` pinned the database at, if any. Each runs in
+/// its own transaction (a savepoint, so it also nests inside a `plan`)
+/// together with its ledger entry, so a failure leaves the database at the
+/// previous migration. Returns the names applied.
+///
+/// Nothing is applied if an already applied migration has been edited since.
+pub(crate) async fn apply(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<Vec<String>, Error> {
+    ensure_ledger(conn).await?;
+    let ledger = read_ledger(conn).await?;
+    check_drift(migrations, &ledger)?;
+    let target = read_target(conn).await?.unwrap_or(u64::MAX);
+    apply_pending(conn, migrations, &ledger, target).await
+}
+
+/// Bring the database to `target`: revert applied migrations above it, newest
+/// first, then apply pending ones up to it.
+///
+/// All reverts run in a single transaction, together with the removal of
+/// their ledger entries, so a failing down script leaves the database where
+/// it was. Nothing is reverted unless every migration to revert has a down
+/// script.
+///
+/// A `target` below the newest migration is recorded, so `apply` stops there
+/// when the database is loaded again; reaching the newest one clears it.
+pub(crate) async fn migrate_to(
+    conn: &Connection,
+    migrations: &[Migration],
+    target: u64,
+) -> Result<MigrateToResult, Error> {
+    ensure_ledger(conn).await?;
+    let ledger = read_ledger(conn).await?;
+    check_drift(migrations, &ledger)?;
+
+    let mut revert: Vec<_> = migrations
+        .iter()
+        .filter(|m| m.version > target && ledger.contains_key(&m.name))
+        .collect();
+    revert.sort_by_key(|m| std::cmp::Reverse(m.version));
+    if let Some(m) = revert.iter().find(|m| m.down.is_none()) {
+        return Err(Error::MigrationIrreversible(m.name.clone()));
+    }
+
+    if !revert.is_empty() {
+        revert_all(conn, &revert).await?;
+    }
+
+    let applied = apply_pending(conn, migrations, &ledger, target).await?;
+    let newest = migrations.iter().map(|m| m.version).max().unwrap_or(0);
+    write_target(conn, (target < newest).then_some(target)).await?;
+
+    Ok(MigrateToResult {
+        reverted: revert.iter().map(|m| m.name.clone()).collect(),
+        applied,
+    })
+}
+
+/// Run the down scripts of `migrations`, in order, in one transaction
+async fn revert_all(conn: &Connection, migrations: &[&Migration]) -> Result<(), Error> {
+    conn.execute("SAVEPOINT migrate", Params::None).await?;
+    let result = async {
+        for migration in migrations {
+            revert_one(conn, migration)
+                .await
+                .map_err(|e| Error::MigrationRevert {
+                    name: migration.name.clone(),
+                    source: Box::new(e),
+                })?;
+        }
+        conn.execute("RELEASE migrate", Params::None).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        rollback_to(conn, "migrate").await;
+    }
+    result
+}
+
+/// Undo and end the savepoint `name`
+async fn rollback_to(conn: &Connection, name: &str) {
+    let _ = conn
+        .execute(&format!("ROLLBACK TO {name}"), Params::None)
+        .await;
+    let _ = conn.execute(&format!("RELEASE {name}"), Params::None).await;
+}
+
+async fn revert_one(conn: &Connection, migration: &Migration) -> Result<(), Error> {
+    run_script(conn, migration.down.as_deref().unwrap_or_default()).await?;
+    conn.execute(
+        &format!("DELETE FROM {MIGRATIONS_TABLE} WHERE hash = ?1"),
+        [migration.name.as_str()],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Apply the migrations missing from `ledger` with a version up to `target`
+async fn apply_pending(
+    conn: &Connection,
+    migrations: &[Migration],
+    ledger: &HashMap<String, Applied>,
+    target: u64,
+) -> Result<Vec<String>, Error> {
+    let mut pending: Vec<_> = migrations
+        .iter()
+        .filter(|m| m.version <= target && !ledger.contains_key(&m.name))
+        .collect();
+    pending.sort_by_key(|m| m.version);
+
+    let mut names = Vec::with_capacity(pending.len());
+    for migration in pending {
+        apply_one(conn, migration)
+            .await
+            .map_err(|e| Error::Migration {
+                name: migration.name.clone(),
+                source: Box::new(e),
+            })?;
+        names.push(migration.name.clone());
+    }
+    Ok(names)
+}
+
+async fn apply_one(conn: &Connection, migration: &Migration) -> Result<(), Error> {
+    conn.execute("SAVEPOINT migrate", Params::None).await?;
+    let result = async {
+        run_script(conn, &migration.sql).await?;
+        conn.execute(
+            &format!("INSERT INTO {MIGRATIONS_TABLE} (hash, checksum) VALUES (?1, ?2)"),
+            [migration.name.clone(), checksum(&migration.sql)],
+        )
+        .await?;
+        conn.execute("RELEASE migrate", Params::None).await?;
+        Ok(())
+    }
+    .await;
+
+    if result.is_err() {
+        rollback_to(conn, "migrate").await;
+    }
+    result
+}
+
+/// Dry-run `migrate_to(target)` — or, without a target, what `apply` would do
+/// on load — and report what it would do. Everything runs for real
+/// inside a savepoint that is then rolled back, so the plan reflects how the
+/// database actually responds, and a migration that would fail fails here.
+pub(crate) async fn plan(
+    conn: &Connection,
+    migrations: &[Migration],
+    target: Option<u64>,
+) -> Result<MigrationPlan, Error> {
+    conn.execute("SAVEPOINT migration_plan", Params::None)
+        .await?;
+    let result = async {
+        let before = schema(conn).await?;
+        let target = match target {
+            Some(target) => target,
+            None => read_target(conn).await?.unwrap_or(u64::MAX),
+        };
+        let outcome = migrate_to(conn, migrations, target).await?;
+        let after = schema(conn).await?;
+        Ok::<_, Error>((outcome, before, after))
+    }
+    .await;
+    rollback_to(conn, "migration_plan").await;
+    let (outcome, before, after) = result?;
+
+    let by_name: HashMap<_, _> = migrations.iter().map(|m| (m.name.as_str(), m)).collect();
+    let step = |name: &String, direction| {
+        let migration = by_name[name.as_str()];
+        let sql = match direction {
+            MigrationDirection::Up => migration.sql.as_str(),
+            MigrationDirection::Down => migration.down.as_deref().unwrap_or_default(),
+        };
+        PlannedMigration {
+            version: migration.version,
+            name: name.clone(),
+            direction,
+            statements: split_statements(sql)
+                .iter()
+                .map(|s| s.sql.to_string())
+                .collect(),
+        }
+    };
+    let steps = outcome
+        .reverted
+        .iter()
+        .map(|name| step(name, MigrationDirection::Down))
+        .chain(
+            outcome
+                .applied
+                .iter()
+                .map(|name| step(name, MigrationDirection::Up)),
+        )
+        .collect();
+
+    Ok(MigrationPlan {
+        migrations: steps,
+        schema_changes: diff_schema(before, after),
+    })
+}
+
+/// `sqlite_master` keyed by object type and name, without SQLite's internal
+/// objects, the ledger and the pinned target
+async fn schema(conn: &Connection) -> Result<BTreeMap<(String, String), Option<String>>, Error> {
+    let mut rows = conn
+        .query(
+            "SELECT type, name, sql FROM sqlite_master
+             WHERE name NOT LIKE 'sqlite\\_%' ESCAPE '\\' AND name NOT IN (?1, ?2)",
+            [MIGRATIONS_TABLE, TARGET_TABLE],
+        )
+        .await?;
+    let mut schema = BTreeMap::new();
+    while let Some(row) = rows.next().await? {
+        schema.insert(
+            (row.get::<String>(0)?, row.get::<String>(1)?),
+            row.get::<Option<String>>(2)?,
+        );
+    }
+    Ok(schema)
+}
+
+fn diff_schema(
+    mut before: BTreeMap<(String, String), Option<String>>,
+    after: BTreeMap<(String, String), Option<String>>,
+) -> Vec<SchemaChange> {
+    let mut changes = Vec::new();
+    for ((object_type, name), sql) in after {
+        let kind = match before.remove(&(object_type.clone(), name.clone())) {
+            None => SchemaChangeKind::Created,
+            Some(old) if old != sql => SchemaChangeKind::Modified,
+            Some(_) => continue,
+        };
+        changes.push(SchemaChange {
+            kind,
+            object_type,
+            name,
+            sql,
+        });
+    }
+    changes.extend(
+        before
+            .into_iter()
+            .map(|((object_type, name), _)| SchemaChange {
+                kind: SchemaChangeKind::Dropped,
+                object_type,
+                name,
+                sql: None,
+            }),
+    );
+    changes
+}
+
+/// State of every configured migration, in version order, followed by applied
+/// migrations that aren't configured (e.g. ones run by the TypeScript
+/// `migrate()`). Read-only: the ledger isn't created or upgraded.
+pub(crate) async fn status(
+    conn: &Connection,
+    migrations: &[Migration],
+) -> Result<Vec<MigrationStatus>, Error> {
+    let mut ledger = read_ledger(conn).await?;
+
+    let mut sorted: Vec<_> = migrations.iter().collect();
+    sorted.sort_by_key(|m| m.version);
+
+    let mut statuses: Vec<_> = sorted
+        .into_iter()
+        .map(|migration| {
+            let current = checksum(&migration.sql);
+            let applied = ledger.remove(&migration.name);
+            let state = match &applied {
+                None => MigrationState::Pending,
+                Some(Applied {
+                    checksum: Some(recorded),
+                    ..
+                }) if *recorded != current => MigrationState::Modified,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: Some(migration.version),
+                name: migration.name.clone(),
+                state,
+                checksum: Some(current),
+                applied_checksum: applied.as_ref().and_then(|a| a.checksum.clone()),
+                applied_at: applied.and_then(|a| a.applied_at),
+            }
+        })
+        .collect();
+
+    let mut unknown: Vec<_> = ledger.into_iter().collect();
+    unknown.sort_by(|a, b| a.0.cmp(&b.0));
+    statuses.extend(unknown.into_iter().map(|(name, applied)| MigrationStatus {
+        version: None,
+        name,
+        state: MigrationState::Applied,
+        checksum: None,
+        applied_checksum: applied.checksum,
+        applied_at: applied.applied_at,
+    }));
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, memory};
+
+    #[test]
+    fn names_files_like_the_typescript_migrator() {
+        let migrations = Migration::from_files([
+            ("drizzle/0001_posts.sql", "b"),
+            ("drizzle/20260605110000_init/migration.sql", "c"),
+            ("drizzle/0000_init.sql", "a"),
+            ("drizzle/meta/_journal.json", "{}"),
+            ("drizzle/notes.sql", "d"),
+            ("drizzle/0001_posts.down.sql", "-b"),
+            ("drizzle/20260605110000_init/down.sql", "-c"),
+        ]);
+        let names: Vec<_> = migrations
+            .iter()
+            .map(|m| (m.version, m.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (0, "0000_init.sql"),
+                (1, "0001_posts.sql"),
+                (20260605110000, "20260605110000_init.sql"),
+            ]
+        );
+        let downs: Vec<_> = migrations.iter().map(|m| m.down.as_deref()).collect();
+        assert_eq!(downs, [None, Some("-b"), Some("-c")]);
+    }
+
+    #[test]
+    fn applies_pending_migrations_once_and_atomically() {
+        block_on(async {
+            let conn = memory().await;
+            let mut migrations = vec![
+                Migration::new(
+                    0,
+                    "0000_init.sql",
+                    "CREATE TABLE t (x);\nINSERT INTO t VALUES (';');",
+                ),
+                Migration::new(
+                    1,
+                    "0001_bad.sql",
+                    "CREATE TABLE u (y);\nINSERT INTO missing VALUES (1);",
+                ),
+            ];
+
+            let err = apply(&conn, &migrations).await.unwrap_err();
+            assert!(matches!(&err, Error::Migration { name, .. } if name == "0001_bad.sql"));
+            // The failing migration left neither its table nor a ledger entry
+            let tables = conn
+                .query(
+                    "SELECT count(*) FROM sqlite_master WHERE name = 'u'",
+                    Params::None,
+                )
+                .await
+                .unwrap()
+                .next()
+                .await
+                .unwrap()
+                .unwrap()
+                .get::<i64>(0)
+                .unwrap();
+            assert_eq!(tables, 0);
+
+            migrations[1].sql = "CREATE TABLE u (y);".into();
+            assert_eq!(apply(&conn, &migrations).await.unwrap(), ["0001_bad.sql"]);
+            assert!(apply(&conn, &migrations).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn reports_migrations_edited_after_they_ran() {
+        block_on(async {
+            let conn = memory().await;
+            // A ledger written by the TypeScript `migrate()`, without checksums
+            conn.execute_batch(&format!(
+                "CREATE TABLE {MIGRATIONS_TABLE} (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    hash TEXT NOT NULL UNIQUE,
+                    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+                );
+                INSERT INTO {MIGRATIONS_TABLE} (hash) VALUES ('0000_init.sql');"
+            ))
+            .await
+            .unwrap();
+
+            let mut migrations = vec![
+                Migration::new(0, "0000_init.sql", "CREATE TABLE t (x);"),
+                Migration::new(1, "0001_u.sql", "CREATE TABLE u (y);"),
+            ];
+            assert_eq!(apply(&conn, &migrations).await.unwrap(), ["0001_u.sql"]);
+
+            // Unverifiable without a recorded checksum, so still applied
+            migrations[0].sql = "CREATE TABLE t (x, z);".into();
+            migrations[1].sql = "CREATE TABLE u (y, z);".into();
+            let states: Vec<_> = status(&conn, &migrations)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| s.state)
+                .collect();
+            assert_eq!(states, [MigrationState::Applied, MigrationState::Modified]);
+
+            let err = apply(&conn, &migrations).await.unwrap_err();
+            assert_eq!(
+                serde_json::to_value(&err).unwrap(),
+                serde_json::json!({
+                    "kind": "migrationModified",
+                    "message": err.to_string(),
+                    "name": "0001_u.sql",
+                    "expected": checksum("CREATE TABLE u (y);"),
+                    "actual": checksum("CREATE TABLE u (y, z);"),
+                })
+            );
+        });
+    }
+
+    #[test]
+    fn migrates_down_and_back_up_to_a_version() {
+        block_on(async {
+            let conn = memory().await;
+            let migrations = vec![
+                Migration::new(0, "0000_t.sql", "CREATE TABLE t (x);"),
+                Migration::new(1, "0001_u.sql", "CREATE TABLE u (y);").with_down("DROP TABLE u;"),
+                Migration::new(2, "0002_v.sql", "CREATE TABLE v (z);").with_down("DROP TABLE v;"),
+            ];
+            apply(&conn, &migrations).await.unwrap();
+
+            let result = migrate_to(&conn, &migrations, 0).await.unwrap();
+            assert_eq!(result.reverted, ["0002_v.sql", "0001_u.sql"]);
+            let pending = status(&conn, &migrations)
+                .await
+                .unwrap()
+                .iter()
+                .filter(|s| s.state == MigrationState::Pending)
+                .count();
+            assert_eq!(pending, 2);
+
+            let result = migrate_to(&conn, &migrations, 1).await.unwrap();
+            assert_eq!(result.applied, ["0001_u.sql"]);
+
+            let without_down = [
+                migrations[0].clone(),
+                Migration::new(1, "0001_u.sql", "CREATE TABLE u (y);"),
+            ];
+            let err = migrate_to(&conn, &without_down, 0).await.unwrap_err();
+            assert!(matches!(err, Error::MigrationIrreversible(name) if name == "0001_u.sql"));
+        });
+    }
+
+    #[test]
+    fn plans_without_changing_the_database() {
+        block_on(async {
+            let conn = memory().await;
+            let migrations = vec![
+                Migration::new(0, "0000_t.sql", "CREATE TABLE t (x);"),
+                Migration::new(
+                    1,
+                    "0001_u.sql",
+                    "CREATE TABLE u (y);\nCREATE INDEX u_y ON u (y);",
+                )
+                .with_down("DROP TABLE u;"),
+            ];
+            apply(&conn, &migrations[..1]).await.unwrap();
+
+            let plan = plan(&conn, &migrations, None).await.unwrap();
+            assert_eq!(plan.migrations.len(), 1);
+            assert_eq!(
+                plan.migrations[0].statements,
+                ["CREATE TABLE u (y);", "CREATE INDEX u_y ON u (y);"]
+            );
+            let changes: Vec<_> = plan
+                .schema_changes
+                .iter()
+                .map(|c| (c.kind, c.name.as_str()))
+                .collect();
+            assert_eq!(
+                changes,
+                [
+                    (SchemaChangeKind::Created, "u_y"),
+                    (SchemaChangeKind::Created, "u")
+                ]
+            );
+
+            let states: Vec<_> = status(&conn, &migrations)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|s| s.state)
+                .collect();
+            assert_eq!(states, [MigrationState::Applied, MigrationState::Pending]);
+            assert_eq!(schema(&conn).await.unwrap().len(), 1);
+        });
+    }
+}
@@ -1,7 +1,9 @@
 use serde::de::DeserializeOwned;
 use std::path::PathBuf;
+use std::time::Duration;
 use tauri::{plugin::PluginApi, AppHandle, Runtime};
 
+use crate::migrate::Migration;
 use crate::models::*;
 
 #[cfg(target_os = "ios")]
@@ -46,4 +48,23 @@ impl Libsql {
     pub fn encryption(&self) -> Option<&EncryptionConfig> {
         self.0.encryption.as_ref()
     }
+
+    /// Get the number of read connections opened per local database
+    pub fn read_pool_size(&self) -> usize {
+        self.0
+            .read_pool_size
+            .unwrap_or(crate::wrapper::DEFAULT_READ_POOL_SIZE)
+    }
+
+    /// Get the migrations to run when the database at `path` is loaded
+    pub fn migrations(&self, path: &str) -> &[Migration] {
+        self.0.migrations.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get how long an interactive transaction may stay open
+    pub fn transaction_timeout(&self) -> Duration {
+        self.0
+            .transaction_timeout
+            .unwrap_or(crate::wrapper::DEFAULT_TRANSACTION_TIMEOUT)
+    }
 }
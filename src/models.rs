@@ -1,4 +1,6 @@
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 
 /// Cipher types for encryption
 #[derive(Debug, Clone, Copy, Deserialize, Serialize)]
@@ -44,6 +46,21 @@ pub struct LoadOptions {
     pub sync_url: Option<String>,
     /// Auth token for remote/Turso connections
     pub auth_token: Option<String>,
+    /// Number of read-only connections to open alongside the writer (local
+    /// files only; switches the database to WAL mode). `0` disables the
+    /// pool. Defaults to the plugin `Config`.
+    pub read_pool_size: Option<usize>,
+    /// How blobs are written in JSON results and recognized in parameters.
+    /// Defaults to `BlobEncoding::Array`; `select` may override it per query.
+    pub blob_encoding: Option<BlobEncoding>,
+    /// How integers beyond JavaScript's safe range are written in JSON
+    /// results. Defaults to `IntegerEncoding::Number`; `select` may override
+    /// it per query.
+    pub integer_encoding: Option<IntegerEncoding>,
+    /// What happens to NaN and ±Infinity in results and `real` parameters.
+    /// Defaults to `NonFinitePolicy::Error`; `select` may override it per
+    /// query.
+    pub non_finite: Option<NonFinitePolicy>,
 }
 
 /// Result of an execute operation
@@ -52,10 +69,517 @@ pub struct LoadOptions {
 pub struct QueryResult {
     /// Number of rows affected
     pub rows_affected: u64,
-    /// Last inserted row ID
+    /// Rowid of the last row inserted on the connection, captured together
+    /// with this statement so concurrent calls can't observe each other's id
     pub last_insert_id: i64,
 }
 
+/// Bound parameters for a statement: either positional (`[1, "a"]`) or named
+/// (`{ "id": 1 }`). Named keys may carry their SQLite prefix (`:id`, `@id`,
+/// `$id`); a bare key binds whichever of the three the statement uses. Keys
+/// the statement doesn't use are ignored and unbound parameters are `NULL`,
+/// as in SQLite.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum QueryValues {
+    Positional(Vec<JsonValue>),
+    Named(serde_json::Map<String, JsonValue>),
+}
+
+impl Default for QueryValues {
+    fn default() -> Self {
+        Self::Positional(Vec::new())
+    }
+}
+
+/// How blob values are represented in JSON.
+///
+/// In either mode a parameter may also be given as
+/// `{ "$type": "blob", "base64": "…" }`, which is always bound as a blob.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BlobEncoding {
+    /// An array of byte values, e.g. `[0, 255]`. Parameters that are arrays
+    /// of numbers are bound as blobs.
+    #[default]
+    Array,
+    /// A standard, padded base64 string in the blob envelope,
+    /// `{ "$type": "blob", "base64": "…" }`, so a blob read in this mode binds
+    /// back as a blob as-is. Bare strings in parameters are bound as text, and
+    /// arrays as JSON text like other non-scalar values. Exports write the
+    /// bare string.
+    Base64,
+}
+
+/// How integers outside JavaScript's safe range (±(2^53 − 1)) are
+/// represented in JSON. Smaller integers are always plain numbers, unless the
+/// query lists their column in `int64_columns`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IntegerEncoding {
+    /// A JSON number, which JavaScript rounds to the nearest double
+    #[default]
+    Number,
+    /// A decimal string, e.g. `"9007199254740993"`
+    String,
+    /// `{ "$type": "int64", "value": "9007199254740993" }`, which guest-js
+    /// turns into a `BigInt`
+    BigInt,
+}
+
+/// What happens to NaN and ±Infinity, which JSON numbers can't represent
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NonFinitePolicy {
+    /// Fail the statement with `UnsupportedDatatype`
+    #[default]
+    Error,
+    /// Read and bind them as NULL
+    Null,
+    /// Read them as `{ "$type": "real", "value": "NaN" }` (or `"Infinity"`,
+    /// `"-Infinity"`), which guest-js turns back into numbers, and bind such
+    /// `real` values as-is
+    Tagged,
+}
+
+/// Shape of the rows returned by `select`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RowFormat {
+    /// One object per row, keyed by column name. Columns sharing a name
+    /// (e.g. from a join) collapse into one key. Carries no column metadata.
+    #[default]
+    Objects,
+    /// `{ columns, metadata, rows }`, each row an array of values in column
+    /// order
+    Columnar,
+}
+
+/// Per-query options for `select`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QueryOptions {
+    pub format: RowFormat,
+    /// Convert values using each column's declared type as well as its
+    /// storage class: integers in `BOOLEAN`/`BOOL` columns become booleans.
+    /// Other declared types (`DATETIME`, …) are returned as stored; read them
+    /// from the columnar `metadata` to convert them yourself.
+    pub typed: bool,
+    /// Overrides the connection's `BlobEncoding` for this query's parameters
+    /// and results
+    pub blob_encoding: Option<BlobEncoding>,
+    /// Overrides the connection's `IntegerEncoding` for this query's results
+    pub integer_encoding: Option<IntegerEncoding>,
+    /// Columns whose integers are all encoded per `IntegerEncoding`, not just
+    /// those outside the safe range. Has no effect with `Number`.
+    pub int64_columns: Vec<String>,
+    /// Overrides the connection's `NonFinitePolicy` for this query
+    pub non_finite: Option<NonFinitePolicy>,
+}
+
+/// File format written by `export_query` and read by `import_file`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FileFormat {
+    #[default]
+    Csv,
+    /// One JSON object per line, keyed by column name
+    Ndjson,
+}
+
+/// Which CSV fields are quoted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CsvQuoting {
+    /// Only fields containing the delimiter, a quote or a line break
+    #[default]
+    Necessary,
+    All,
+}
+
+/// Options for `export_query`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ExportOptions {
+    pub format: FileFormat,
+    /// Write the column names as the first CSV record. Default: `true`
+    pub header: bool,
+    /// CSV field delimiter. Default: `,`
+    pub delimiter: char,
+    pub quote: CsvQuoting,
+    /// Defaults to `Base64` rather than the connection's encoding, since byte
+    /// arrays are unwieldy in a file
+    pub blob_encoding: Option<BlobEncoding>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: FileFormat::default(),
+            header: true,
+            delimiter: ',',
+            quote: CsvQuoting::default(),
+            blob_encoding: None,
+        }
+    }
+}
+
+/// Options for `import_file`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImportOptions {
+    pub format: FileFormat,
+    /// The first CSV record holds the field names. Default: `true`
+    pub header: bool,
+    /// CSV field delimiter. Default: `,`
+    pub delimiter: char,
+    /// File field → table column. Only mapped fields are imported. Without
+    /// it, every CSV header field, or every key of the first well-formed
+    /// NDJSON object, goes to the column of the same name. Fields of a CSV
+    /// file without a header are named by their one-based position: `"1"`,
+    /// `"2"`, …
+    pub columns: Option<IndexMap<String, String>>,
+    /// Create the table, with untyped columns, if it doesn't exist. An empty
+    /// file needs `columns` to name them.
+    pub create_table: bool,
+    /// Report records that fail and carry on, rather than rolling back the
+    /// whole import
+    pub skip_errors: bool,
+    /// Records between progress events. Default: 1000
+    pub progress_interval: u64,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            format: FileFormat::default(),
+            header: true,
+            delimiter: ',',
+            columns: None,
+            create_table: false,
+            skip_errors: false,
+            progress_interval: 1000,
+        }
+    }
+}
+
+/// Message pushed over an `import_file` channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum ImportEvent {
+    #[serde(rename_all = "camelCase")]
+    Progress {
+        rows_imported: u64,
+        rows_failed: u64,
+        bytes_read: u64,
+        total_bytes: u64,
+    },
+    /// A record was skipped (with `skip_errors`)
+    LineError {
+        /// One-based line the record starts on
+        line: u64,
+        message: String,
+    },
+}
+
+/// Result of `import_file`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportResult {
+    pub rows_imported: u64,
+    /// Records skipped with `skip_errors`
+    pub rows_failed: u64,
+}
+
+/// Message pushed over a `backup` channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum BackupEvent {
+    /// Bytes of the copy written so far, against the used size of the
+    /// database
+    #[serde(rename_all = "camelCase")]
+    Progress {
+        bytes_written: u64,
+        total_bytes: u64,
+    },
+    /// The copy is complete and its integrity is being checked
+    Verifying,
+}
+
+/// Declared type and origin of a result column. Only columnar results carry
+/// it: `select` with `RowFormat::Columnar`, `query` and `select_binary`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnMetadata {
+    /// Name of the column in the result set
+    pub name: String,
+    /// Type from the column's declaration, e.g. `INTEGER` or `DATETIME`.
+    /// `None` for expressions and for remote databases.
+    pub decl_type: Option<String>,
+    /// Table the column is read from (`None` for expressions)
+    pub table: Option<String>,
+    /// Name of the column in that table (`None` for expressions)
+    pub column: Option<String>,
+    /// Schema the table lives in, e.g. `main` (`None` for expressions)
+    pub database: Option<String>,
+}
+
+/// Rows of a `select` in the columnar format
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnarResult {
+    /// Column names in order, duplicates included
+    pub columns: Vec<String>,
+    /// Declared type and origin of each column, in the same order
+    pub metadata: Vec<ColumnMetadata>,
+    pub rows: Vec<Vec<JsonValue>>,
+}
+
+/// Result of a `select`, in the requested `RowFormat`
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum SelectResult {
+    Objects(Vec<IndexMap<String, JsonValue>>),
+    Columnar(ColumnarResult),
+}
+
+/// Result of `query`: the rows of a statement that returns columns, together
+/// with what it wrote. Both parts are always present; `rows` is empty for
+/// statements without columns.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StatementResult {
+    #[serde(flatten)]
+    pub rows: ColumnarResult,
+    /// Rows inserted, updated or deleted by the statement itself
+    pub rows_affected: u64,
+    /// Rowid of the last row inserted on the connection
+    pub last_insert_id: i64,
+}
+
+/// Result of `execute_script`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptResult {
+    /// Number of statements the script contained
+    pub statements: usize,
+    /// Rows inserted, updated or deleted by the script's statements, summed
+    /// as `execute` counts them (rows changed by triggers aren't included)
+    pub rows_affected: u64,
+}
+
+/// Result of `export_sql`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpResult {
+    /// Number of tables dumped
+    pub tables: usize,
+    /// Number of rows dumped, across all tables
+    pub rows: u64,
+}
+
+/// A single statement of a parameterized batch
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchStatement {
+    pub query: String,
+    /// Bound parameters, same as for `execute`/`select`
+    #[serde(default)]
+    pub values: QueryValues,
+}
+
+/// Result of one statement of a parameterized batch
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum BatchResult {
+    /// Rows of a statement that returns columns (SELECT, `RETURNING`, …)
+    Rows(Vec<IndexMap<String, JsonValue>>),
+    /// Outcome of a statement that returns no rows
+    Execute(QueryResult),
+}
+
+/// Message pushed over a `select_stream` channel
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum StreamEvent {
+    /// The next chunk of rows
+    Rows(Vec<IndexMap<String, JsonValue>>),
+    /// Every row has been sent
+    #[serde(rename_all = "camelCase")]
+    Done { total_rows: u64 },
+    /// Reading the cursor failed; nothing follows
+    Error(String),
+}
+
+/// Where a migration stands against a database's ledger
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    /// Applied, but its SQL has changed since
+    Modified,
+}
+
+/// One migration in a `migration_status` listing
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationStatus {
+    /// `None` for applied migrations that aren't configured in Rust
+    pub version: Option<u64>,
+    pub name: String,
+    pub state: MigrationState,
+    /// Checksum of the configured SQL
+    pub checksum: Option<String>,
+    /// Checksum recorded when the migration was applied, if any
+    pub applied_checksum: Option<String>,
+    pub applied_at: Option<String>,
+}
+
+/// Result of `migrate_to`
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrateToResult {
+    /// Migrations reverted, newest first
+    pub reverted: Vec<String>,
+    /// Migrations applied, oldest first
+    pub applied: Vec<String>,
+}
+
+/// Whether a planned migration is applied or reverted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationDirection {
+    Up,
+    Down,
+}
+
+/// A migration a `migration_plan` would run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedMigration {
+    pub version: u64,
+    pub name: String,
+    pub direction: MigrationDirection,
+    /// The statements of its up or down script, in order
+    pub statements: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SchemaChangeKind {
+    Created,
+    Modified,
+    Dropped,
+}
+
+/// A schema object (table, index, view or trigger) a plan would change
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaChange {
+    pub kind: SchemaChangeKind,
+    /// `sqlite_master.type`: `table`, `index`, `view` or `trigger`
+    pub object_type: String,
+    pub name: String,
+    /// Definition after the plan runs; `None` when dropped
+    pub sql: Option<String>,
+}
+
+/// Result of a `migration_plan` dry run
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationPlan {
+    /// Migrations in the order they would run: reverts first, newest first,
+    /// then applies, oldest first
+    pub migrations: Vec<PlannedMigration>,
+    pub schema_changes: Vec<SchemaChange>,
+}
+
+/// A schema (`main`, `temp` or an attached database), as `introspect` sees it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaInfo {
+    pub name: String,
+    /// Database file; `None` for in-memory and temporary databases
+    pub file: Option<String>,
+    pub tables: Vec<TableInfo>,
+    pub views: Vec<ViewInfo>,
+    pub triggers: Vec<TriggerInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableInfo {
+    pub name: String,
+    /// The `CREATE TABLE` statement
+    pub sql: Option<String>,
+    pub columns: Vec<ColumnInfo>,
+    pub indexes: Vec<IndexInfo>,
+    pub foreign_keys: Vec<ForeignKeyInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ViewInfo {
+    pub name: String,
+    pub sql: Option<String>,
+    pub columns: Vec<ColumnInfo>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnInfo {
+    pub name: String,
+    /// Declared type, e.g. `"INTEGER"`; `None` if the column has none
+    pub decl_type: Option<String>,
+    pub not_null: bool,
+    /// Default value as SQL text, e.g. `"'untitled'"` or `"CURRENT_TIMESTAMP"`
+    pub default_value: Option<String>,
+    /// 1-based position in the primary key; `None` if not part of it
+    pub primary_key: Option<u32>,
+    /// Whether this is a generated column
+    pub generated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexInfo {
+    pub name: String,
+    pub unique: bool,
+    /// `"c"` for `CREATE INDEX`, `"u"` for a UNIQUE constraint, `"pk"` for
+    /// the primary key
+    pub origin: String,
+    /// Whether the index has a `WHERE` clause
+    pub partial: bool,
+    /// Indexed columns in order; `None` for expressions
+    pub columns: Vec<Option<String>>,
+    /// The `CREATE INDEX` statement; `None` for indexes SQLite creates itself
+    pub sql: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForeignKeyInfo {
+    /// Referenced table
+    pub table: String,
+    /// Referencing columns of this table
+    pub columns: Vec<String>,
+    /// Referenced columns, in the same order; `None` means the referenced
+    /// table's primary key
+    pub references: Vec<Option<String>>,
+    pub on_update: String,
+    pub on_delete: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerInfo {
+    pub name: String,
+    /// Table or view the trigger fires on
+    pub table: String,
+    pub sql: Option<String>,
+}
+
 // Keep ping for backwards compatibility
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
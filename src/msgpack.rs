@@ -0,0 +1,224 @@
+//! Minimal MessagePack encoder for binary `select` responses.
+//!
+//! Only what's needed to serialize libsql values and result headers is
+//! implemented. The matching decoder is `guest-js/msgpack.ts`.
+
+use libsql::Value;
+
+/// Appends MessagePack-encoded values to a byte buffer
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Append bytes that are already MessagePack-encoded
+    pub fn raw(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn nil(&mut self) {
+        self.buf.push(0xc0);
+    }
+
+    pub fn bool(&mut self, b: bool) {
+        self.buf.push(if b { 0xc3 } else { 0xc2 });
+    }
+
+    /// Encode an integer in the smallest format that holds it
+    pub fn int(&mut self, i: i64) {
+        match i {
+            0..=0x7f => self.buf.push(i as u8),
+            -32..=-1 => self.buf.push(i as i8 as u8),
+            0x80..=0xff => self.tagged(0xcc, &(i as u8).to_be_bytes()),
+            0x100..=0xffff => self.tagged(0xcd, &(i as u16).to_be_bytes()),
+            0x1_0000..=0xffff_ffff => self.tagged(0xce, &(i as u32).to_be_bytes()),
+            0x1_0000_0000.. => self.tagged(0xcf, &(i as u64).to_be_bytes()),
+            -0x80..=-33 => self.tagged(0xd0, &(i as i8).to_be_bytes()),
+            -0x8000..=-0x81 => self.tagged(0xd1, &(i as i16).to_be_bytes()),
+            -0x8000_0000..=-0x8001 => self.tagged(0xd2, &(i as i32).to_be_bytes()),
+            _ => self.tagged(0xd3, &i.to_be_bytes()),
+        }
+    }
+
+    pub fn float(&mut self, f: f64) {
+        self.tagged(0xcb, &f.to_be_bytes());
+    }
+
+    pub fn str(&mut self, s: &str) {
+        let len = s.len();
+        match len {
+            0..=31 => self.buf.push(0xa0 | len as u8),
+            32..=0xff => self.tagged(0xd9, &(len as u8).to_be_bytes()),
+            0x100..=0xffff => self.tagged(0xda, &(len as u16).to_be_bytes()),
+            _ => self.tagged(0xdb, &(len as u32).to_be_bytes()),
+        }
+        self.buf.extend_from_slice(s.as_bytes());
+    }
+
+    pub fn bin(&mut self, bytes: &[u8]) {
+        let len = bytes.len();
+        match len {
+            0..=0xff => self.tagged(0xc4, &(len as u8).to_be_bytes()),
+            0x100..=0xffff => self.tagged(0xc5, &(len as u16).to_be_bytes()),
+            _ => self.tagged(0xc6, &(len as u32).to_be_bytes()),
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn opt_str(&mut self, s: Option<&str>) {
+        match s {
+            Some(s) => self.str(s),
+            None => self.nil(),
+        }
+    }
+
+    /// Start an array; the next `len` values are its elements
+    pub fn array(&mut self, len: usize) {
+        match len {
+            0..=15 => self.buf.push(0x90 | len as u8),
+            16..=0xffff => self.tagged(0xdc, &(len as u16).to_be_bytes()),
+            _ => self.tagged(0xdd, &(len as u32).to_be_bytes()),
+        }
+    }
+
+    /// Start a map; the next `len` key/value pairs are its entries
+    pub fn map(&mut self, len: usize) {
+        match len {
+            0..=15 => self.buf.push(0x80 | len as u8),
+            16..=0xffff => self.tagged(0xde, &(len as u16).to_be_bytes()),
+            _ => self.tagged(0xdf, &(len as u32).to_be_bytes()),
+        }
+    }
+
+    /// Encode a libsql value as-is: blobs stay binary and non-finite reals
+    /// are representable, unlike in JSON.
+    pub fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.nil(),
+            Value::Integer(i) => self.int(*i),
+            Value::Real(f) => self.float(*f),
+            Value::Text(s) => self.str(s),
+            Value::Blob(bytes) => self.bin(bytes),
+        }
+    }
+
+    fn tagged(&mut self, tag: u8, payload: &[u8]) {
+        self.buf.push(tag);
+        self.buf.extend_from_slice(payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(write: impl FnOnce(&mut Encoder)) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        write(&mut encoder);
+        encoder.into_bytes()
+    }
+
+    #[test]
+    fn integers_use_the_smallest_format() {
+        let cases: &[(i64, &[u8])] = &[
+            (0, &[0x00]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0xcc, 0x80]),
+            (0xff, &[0xcc, 0xff]),
+            (0x100, &[0xcd, 0x01, 0x00]),
+            (0xffff, &[0xcd, 0xff, 0xff]),
+            (0x1_0000, &[0xce, 0x00, 0x01, 0x00, 0x00]),
+            (0xffff_ffff, &[0xce, 0xff, 0xff, 0xff, 0xff]),
+            (0x1_0000_0000, &[0xcf, 0, 0, 0, 0x01, 0, 0, 0, 0]),
+            (
+                i64::MAX,
+                &[0xcf, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (-1, &[0xff]),
+            (-32, &[0xe0]),
+            (-33, &[0xd0, 0xdf]),
+            (-0x80, &[0xd0, 0x80]),
+            (-0x81, &[0xd1, 0xff, 0x7f]),
+            (-0x8000, &[0xd1, 0x80, 0x00]),
+            (-0x8001, &[0xd2, 0xff, 0xff, 0x7f, 0xff]),
+            (-0x8000_0000, &[0xd2, 0x80, 0x00, 0x00, 0x00]),
+            (
+                -0x8000_0001,
+                &[0xd3, 0xff, 0xff, 0xff, 0xff, 0x7f, 0xff, 0xff, 0xff],
+            ),
+            (i64::MIN, &[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ];
+        for &(i, expected) in cases {
+            assert_eq!(encode(|e| e.int(i)), expected, "{i}");
+        }
+    }
+
+    #[test]
+    fn lengths_pick_the_header_width() {
+        let cases: &[(usize, &[u8], &[u8])] = &[
+            (0, &[0xa0], &[0xc4, 0x00]),
+            (31, &[0xbf], &[0xc4, 0x1f]),
+            (32, &[0xd9, 0x20], &[0xc4, 0x20]),
+            (0xff, &[0xd9, 0xff], &[0xc4, 0xff]),
+            (0x100, &[0xda, 0x01, 0x00], &[0xc5, 0x01, 0x00]),
+            (0xffff, &[0xda, 0xff, 0xff], &[0xc5, 0xff, 0xff]),
+            (
+                0x1_0000,
+                &[0xdb, 0x00, 0x01, 0x00, 0x00],
+                &[0xc6, 0x00, 0x01, 0x00, 0x00],
+            ),
+        ];
+        for &(len, str_header, bin_header) in cases {
+            let s = "x".repeat(len);
+            let encoded = encode(|e| e.str(&s));
+            assert_eq!(&encoded[..str_header.len()], str_header, "str of {len}");
+            assert_eq!(&encoded[str_header.len()..], s.as_bytes());
+
+            let bytes = vec![0xab; len];
+            let encoded = encode(|e| e.value(&Value::Blob(bytes.clone())));
+            assert_eq!(&encoded[..bin_header.len()], bin_header, "bin of {len}");
+            assert_eq!(&encoded[bin_header.len()..], bytes);
+        }
+
+        let containers: &[(usize, &[u8], &[u8])] = &[
+            (15, &[0x9f], &[0x8f]),
+            (16, &[0xdc, 0x00, 0x10], &[0xde, 0x00, 0x10]),
+            (0xffff, &[0xdc, 0xff, 0xff], &[0xde, 0xff, 0xff]),
+            (
+                0x1_0000,
+                &[0xdd, 0x00, 0x01, 0x00, 0x00],
+                &[0xdf, 0x00, 0x01, 0x00, 0x00],
+            ),
+        ];
+        for &(len, array, map) in containers {
+            assert_eq!(encode(|e| e.array(len)), array, "array of {len}");
+            assert_eq!(encode(|e| e.map(len)), map, "map of {len}");
+        }
+    }
+
+    #[test]
+    fn scalars_encode_exactly() {
+        assert_eq!(encode(|e| e.value(&Value::Null)), [0xc0]);
+        assert_eq!(encode(|e| e.bool(false)), [0xc2]);
+        assert_eq!(encode(|e| e.bool(true)), [0xc3]);
+        assert_eq!(
+            encode(|e| e.value(&Value::Real(1.5))),
+            [0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            encode(|e| e.float(f64::NEG_INFINITY)),
+            [0xcb, 0xff, 0xf0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(encode(|e| e.opt_str(None)), [0xc0]);
+        assert_eq!(encode(|e| e.opt_str(Some("é"))), [0xa2, 0xc3, 0xa9]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Splitting SQL scripts into statements.
+//!
+//! Statement boundaries follow SQLite's own `sqlite3_complete()`: a `;` ends
+//! a statement unless it is inside a string, quoted identifier or comment, or
+//! inside the body of a `CREATE TRIGGER … BEGIN … END`.
+//!
+//! This is a port rather than a call into libsql because nothing there fits:
+//! `execute_batch` doesn't say which statement failed or where it starts,
+//! which `ScriptStatement` errors and migration plans report, and
+//! `sqlite3_complete()` isn't exposed. Reading a dump line by line also needs
+//! `is_complete` to know when a statement has ended.
+
+/// One statement of a script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScriptStatement<'a> {
+    /// Statement text, including its terminating `;` if it has one
+    pub sql: &'a str,
+    /// Byte offset of the statement in the script
+    pub offset: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Semi,
+    Whitespace,
+    Other,
+    Explain,
+    Create,
+    Temp,
+    Trigger,
+    End,
+}
+
+/// `sqlite3_complete()` state transitions, indexed by `[state][token]`.
+/// States: 0 invalid, 1 start, 2 normal, 3 explain, 4 create, 5 trigger,
+/// 6 semi (inside a trigger), 7 end (inside a trigger).
+const TRANSITIONS: [[u8; 8]; 8] = [
+    //  SEMI WS OTHER EXPLAIN CREATE TEMP TRIGGER END
+    [1, 0, 2, 3, 4, 2, 2, 2],
+    [1, 1, 2, 3, 4, 2, 2, 2],
+    [1, 2, 2, 2, 2, 2, 2, 2],
+    [1, 3, 3, 2, 4, 2, 2, 2],
+    [1, 4, 2, 2, 2, 4, 5, 2],
+    [6, 5, 5, 5, 5, 5, 5, 5],
+    [6, 6, 5, 5, 5, 5, 5, 7],
+    [1, 7, 5, 5, 5, 5, 5, 5],
+];
+
+const START: u8 = 1;
+
+/// Split `script` into its statements, skipping empty ones (e.g. `;;` or a
+/// trailing comment). A final statement without a `;` is included as-is, so
+/// an unterminated one still reaches SQLite and fails there.
+pub fn split_statements(script: &str) -> Vec<ScriptStatement<'_>> {
+    let bytes = script.as_bytes();
+    let mut statements = Vec::new();
+    let mut state = 0;
+    let mut start = None;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let (token, end) = next_token(bytes, pos);
+        state = TRANSITIONS[state as usize][token as usize];
+
+        match token {
+            Token::Semi if state == START => {
+                if let Some(offset) = start.take() {
+                    statements.push(ScriptStatement {
+                        sql: &script[offset..end],
+                        offset,
+                    });
+                }
+            }
+            Token::Whitespace => {}
+            _ => {
+                start.get_or_insert(pos);
+            }
+        }
+        pos = end;
+    }
+
+    if let Some(offset) = start {
+        statements.push(ScriptStatement {
+            sql: script[offset..].trim_end(),
+            offset,
+        });
+    }
+    statements
+}
+
+/// Whether `sql` ends with a complete statement, i.e. it is terminated by a
+/// `;` outside literals, comments and trigger bodies (trailing whitespace and
+/// comments allowed), like SQLite's `sqlite3_complete()`
+pub fn is_complete(sql: &str) -> bool {
+    let bytes = sql.as_bytes();
+    let mut state = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let (token, end) = next_token(bytes, pos);
+        state = TRANSITIONS[state as usize][token as usize];
+        pos = end;
+    }
+    state == START
+}
+
+/// Quote an identifier (table, column or schema name) for use in SQL
+pub fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Read the token starting at `pos`; returns it and the offset just past it.
+/// Unterminated comments, strings and identifiers run to the end of input.
+fn next_token(bytes: &[u8], pos: usize) -> (Token, usize) {
+    let rest = &bytes[pos..];
+    let find = |from: usize, needle: &[u8]| {
+        rest[from..]
+            .windows(needle.len())
+            .position(|w| w == needle)
+            .map_or(bytes.len(), |i| pos + from + i + needle.len())
+    };
+
+    match rest[0] {
+        b';' => (Token::Semi, pos + 1),
+        b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' => (Token::Whitespace, pos + 1),
+        b'/' if rest.get(1) == Some(&b'*') => (Token::Whitespace, find(2, b"*/")),
+        b'-' if rest.get(1) == Some(&b'-') => (Token::Whitespace, find(2, b"\n")),
+        b'[' => (Token::Other, find(1, b"]")),
+        quote @ (b'\'' | b'"' | b'`') => (Token::Other, find(1, &[quote])),
+        c if is_id_char(c) => {
+            let len = rest.iter().take_while(|&&c| is_id_char(c)).count();
+            let word = &rest[..len];
+            let token = if word.eq_ignore_ascii_case(b"create") {
+                Token::Create
+            } else if word.eq_ignore_ascii_case(b"trigger") {
+                Token::Trigger
+            } else if word.eq_ignore_ascii_case(b"temp") || word.eq_ignore_ascii_case(b"temporary")
+            {
+                Token::Temp
+            } else if word.eq_ignore_ascii_case(b"end") {
+                Token::End
+            } else if word.eq_ignore_ascii_case(b"explain") {
+                Token::Explain
+            } else {
+                Token::Other
+            };
+            (token, pos + len)
+        }
+        _ => (Token::Other, pos + 1),
+    }
+}
+
+/// Identifier characters. Every byte of a multi-byte UTF-8 sequence counts, as
+/// in SQLite, so token boundaries always fall on char boundaries.
+fn is_id_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'$' || c >= 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(script: &str) -> Vec<&str> {
+        split_statements(script).iter().map(|s| s.sql).collect()
+    }
+
+    #[test]
+    fn splits_on_semicolons_outside_literals_and_comments() {
+        assert_eq!(
+            split("INSERT INTO t VALUES ('a;b'); -- c;d\nSELECT \"x;\" /* ; */ FROM [t;];;"),
+            [
+                "INSERT INTO t VALUES ('a;b');",
+                "SELECT \"x;\" /* ; */ FROM [t;];",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_trigger_bodies_together() {
+        let script = "CREATE TEMP TRIGGER tr AFTER INSERT ON t BEGIN\n  \
+                      UPDATE t SET n = n + 1;\n  DELETE FROM u;\nEND;\nSELECT 1";
+        assert_eq!(
+            split(script),
+            [
+                "CREATE TEMP TRIGGER tr AFTER INSERT ON t BEGIN\n  UPDATE t SET n = n + 1;\n  DELETE FROM u;\nEND;",
+                "SELECT 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn detects_complete_statements() {
+        assert!(is_complete("SELECT 1; -- done\n"));
+        assert!(!is_complete("SELECT ';"));
+        assert!(!is_complete(
+            "CREATE TRIGGER tr AFTER INSERT ON t BEGIN SELECT 1;"
+        ));
+        assert!(is_complete(
+            "CREATE TRIGGER tr AFTER INSERT ON t BEGIN SELECT 1; END;"
+        ));
+    }
+
+    #[test]
+    fn reports_byte_offsets() {
+        let offsets: Vec<_> = split_statements("  SELECT 'é';\n\nSELECT 2;")
+            .iter()
+            .map(|s| s.offset)
+            .collect();
+        assert_eq!(offsets, [2, 16]);
+    }
+}
@@ -0,0 +1,35 @@
+//! Helpers shared by the unit tests
+
+use libsql::Connection;
+use std::future::Future;
+use std::path::PathBuf;
+
+/// Run a test's body on a multi-threaded runtime, so spawned tasks and
+/// timers work as they do in the app
+pub(crate) fn block_on<F: Future>(test: F) -> F::Output {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(test)
+}
+
+/// A fresh, empty directory for a test's files
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("tauri-plugin-libsql-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A connection to a new, private in-memory database
+pub(crate) async fn memory() -> Connection {
+    libsql::Builder::new_local(":memory:")
+        .build()
+        .await
+        .unwrap()
+        .connect()
+        .unwrap()
+}
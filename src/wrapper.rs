@@ -1,21 +1,119 @@
-use futures::lock::Mutex;
+use futures::lock::{Mutex, MutexGuard, OwnedMutexGuard};
 use futures::FutureExt;
 use indexmap::IndexMap;
-use libsql::{params::Params, Builder as LibsqlBuilder, Connection, Database, Value};
+use libsql::{
+    params::Params, Builder as LibsqlBuilder, Connection, Database, Row, Rows, Statement,
+};
 use serde_json::Value as JsonValue;
 use std::collections::HashMap;
+use std::fs::File;
+use std::future::Future;
+use std::io::{BufReader, BufWriter, Seek, SeekFrom};
+use std::ops::Deref;
 use std::panic::AssertUnwindSafe;
 use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
 
-use crate::decode;
+use crate::decode::{Decoder, Encodings};
+use crate::encode::json_to_params;
 use crate::error::Error;
-use crate::models::{EncryptionConfig, QueryResult};
+use crate::migrate::Migration;
+use crate::models::{
+    BackupEvent, BatchResult, BatchStatement, BlobEncoding, ColumnMetadata, ColumnarResult,
+    DumpResult, EncryptionConfig, ExportOptions, ImportEvent, ImportOptions, ImportResult,
+    IntegerEncoding, MigrateToResult, MigrationPlan, MigrationStatus, QueryOptions, QueryResult,
+    QueryValues, RowFormat, SchemaInfo, ScriptResult, SelectResult, StatementResult, StreamEvent,
+};
+use crate::msgpack::Encoder;
+use crate::script::split_statements;
 
-/// A wrapper around libsql connection
+/// How long an interactive transaction may stay open before it is rolled back
+/// automatically, unless overridden in `Config` or per `begin_transaction` call.
+pub const DEFAULT_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Number of read connections opened per local database file, unless
+/// overridden in `Config` or `LoadOptions`. The pool is opt-in, since it
+/// switches the database to WAL mode.
+pub const DEFAULT_READ_POOL_SIZE: usize = 0;
+
+/// Most read connections opened per database, whatever was asked for
+pub const MAX_READ_POOL_SIZE: usize = 16;
+
+/// Rows per chunk pushed by `select_stream`, unless the caller asks otherwise.
+pub const DEFAULT_STREAM_CHUNK_SIZE: usize = 256;
+
+/// Chunks a stream may push ahead of the consumer's acknowledgements.
+const STREAM_WINDOW: usize = 2;
+
+/// A stream whose consumer neither acknowledges nor cancels it for this long
+/// is dropped, so a closed view can't pin a connection.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long `restore` waits for in-flight statements and cancelled streams to
+/// let go of the connection before giving up
+const RESTORE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Primary result code for most SQL errors, e.g. a missing table
+const SQLITE_ERROR: i32 = 1;
+
+/// Primary result code SQLite reports when a read connection attempts a write
+const SQLITE_READONLY: i32 = 8;
+
+/// The writer connection as held by an open transaction. `None` once the
+/// transaction has been committed or rolled back.
+type TransactionSlot = Arc<Mutex<Option<OwnedMutexGuard<Connection>>>>;
+
+type Transactions = Arc<Mutex<HashMap<u64, TransactionSlot>>>;
+
+/// Open `select_stream` cursors, keyed by id. Each semaphore holds the chunks
+/// the stream may still send before it has to wait for an acknowledgement.
+type Streams = Arc<Mutex<HashMap<u64, Arc<Semaphore>>>>;
+
+/// A wrapper around libsql connection.
+///
+/// Local database files get one writer, plus an optional pool of read-only
+/// connections in WAL mode so `select` doesn't queue behind writes. Everything
+/// else — and every statement inside a transaction — runs on the writer.
 pub struct DbConnection {
-    conn: Connection,
+    conn: Arc<Mutex<Connection>>,
+    readers: Vec<Arc<Mutex<Connection>>>,
+    next_reader: AtomicUsize,
     db: Database,
+    transactions: Transactions,
+    next_transaction_id: AtomicU64,
+    streams: Streams,
+    next_stream_id: AtomicU64,
+    /// Open streams whose cursor is on the writer, which no transaction may
+    /// begin on until they end
+    writer_streams: Arc<AtomicUsize>,
+    encodings: Encodings,
+    /// Database file of a local database or embedded replica
+    file: Option<PathBuf>,
+    replica: bool,
+    encryption: Option<EncryptionConfig>,
+    stream_idle_timeout: Duration,
+}
+
+/// A locked connection that a single statement runs on: either a shared
+/// connection (the writer or a reader), or the one owned by an open transaction.
+enum ConnGuard<'a> {
+    Shared(MutexGuard<'a, Connection>),
+    Transaction(OwnedMutexGuard<Option<OwnedMutexGuard<Connection>>>),
+}
+
+impl Deref for ConnGuard<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            ConnGuard::Shared(conn) => conn,
+            // `DbConnection::acquire` never hands out a finished transaction
+            ConnGuard::Transaction(slot) => slot.as_deref().expect("transaction already finished"),
+        }
+    }
 }
 
 impl DbConnection {
@@ -24,13 +122,39 @@ impl DbConnection {
     /// - Local only: `sync_url` = None
     /// - Embedded replica (Turso): `sync_url` = Some("libsql://…"), `auth_token` = Some("…")
     /// - Pure remote: `path` starts with "libsql://" or "https://", no `sync_url`
+    ///
+    /// `read_pool_size` read connections, up to `MAX_READ_POOL_SIZE`, are
+    /// opened for local database files, switching the file to WAL mode for
+    /// good; `0` disables the pool and leaves the journal mode untouched.
+    /// `encodings` apply to every statement that doesn't override them.
     pub async fn connect(
         path: &str,
         encryption: Option<EncryptionConfig>,
         base_path: PathBuf,
         sync_url: Option<String>,
         auth_token: Option<String>,
+        read_pool_size: usize,
+        encodings: Encodings,
     ) -> Result<Self, Error> {
+        let is_remote = path.starts_with("libsql://") || path.starts_with("https://");
+        // In-memory databases are private to each connection, and replicas and
+        // remote databases don't benefit from a local read pool.
+        let is_memory = path.strip_prefix("sqlite:").unwrap_or(path) == ":memory:";
+        let read_pool_size = if sync_url.is_none() && !is_remote && !is_memory {
+            read_pool_size.min(MAX_READ_POOL_SIZE)
+        } else {
+            0
+        };
+
+        // Kept for backup and restore: the database file and its key
+        let replica = sync_url.is_some();
+        let file = if is_remote || is_memory {
+            None
+        } else {
+            Some(Self::resolve_local_path(path, &base_path)?)
+        };
+        let key = encryption.clone();
+
         // Wrap in catch_unwind: libsql's builder calls unwrap() internally and can
         // panic on a malformed URL, which would cause the Tauri IPC to hang forever.
         let path = path.to_string();
@@ -38,7 +162,7 @@ impl DbConnection {
             if let Some(url) = sync_url {
                 let full_path = Self::resolve_local_path(&path, &base_path)?;
                 Self::open_replica(full_path, url, auth_token.unwrap_or_default(), encryption).await
-            } else if path.starts_with("libsql://") || path.starts_with("https://") {
+            } else if is_remote {
                 Self::open_remote(path, auth_token.unwrap_or_default()).await
             } else {
                 let full_path = Self::resolve_local_path(&path, &base_path)?;
@@ -56,7 +180,35 @@ impl DbConnection {
         })??;
 
         let conn = db.connect()?;
-        Ok(Self { conn, db })
+
+        let mut readers = Vec::with_capacity(read_pool_size);
+        if read_pool_size > 0 {
+            // WAL lets readers see the last commit while the writer is busy.
+            // The mode is stored in the file, so it stays WAL after closing.
+            conn.query("PRAGMA journal_mode=WAL", Params::None).await?;
+            for _ in 0..read_pool_size {
+                let reader = db.connect()?;
+                reader.execute("PRAGMA query_only=1", Params::None).await?;
+                readers.push(Arc::new(Mutex::new(reader)));
+            }
+        }
+
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            readers,
+            next_reader: AtomicUsize::new(0),
+            db,
+            transactions: Arc::default(),
+            next_transaction_id: AtomicU64::new(1),
+            streams: Arc::default(),
+            next_stream_id: AtomicU64::new(1),
+            writer_streams: Arc::default(),
+            encodings,
+            file,
+            replica,
+            encryption: key,
+            stream_idle_timeout: STREAM_IDLE_TIMEOUT,
+        })
     }
 
     // ── connection mode helpers ──────────────────────────────────────────────
@@ -73,17 +225,7 @@ impl DbConnection {
         }
 
         // Normalise away `..` so a path can't escape base_path
-        let joined = base_path.join(db_path);
-        let normalised = joined.components().fold(PathBuf::new(), |mut acc, c| {
-            match c {
-                Component::ParentDir => {
-                    acc.pop();
-                }
-                Component::CurDir => {}
-                _ => acc.push(c),
-            }
-            acc
-        });
+        let normalised = normalise(&base_path.join(db_path));
 
         if !normalised.starts_with(base_path) {
             return Err(Error::InvalidDbUrl(format!(
@@ -100,7 +242,7 @@ impl DbConnection {
         encryption: Option<EncryptionConfig>,
     ) -> Result<Database, Error> {
         #[allow(unused_mut)]
-        let mut builder = LibsqlBuilder::new_local(&full_path.to_string_lossy().to_string());
+        let mut builder = LibsqlBuilder::new_local(full_path.to_string_lossy().to_string());
 
         #[cfg(feature = "encryption")]
         if let Some(config) = encryption {
@@ -186,14 +328,59 @@ impl DbConnection {
         ))
     }
 
+    /// Lock the connection a statement should run on. Statements outside a
+    /// transaction wait for any open transaction to finish first.
+    async fn acquire(&self, transaction_id: Option<u64>) -> Result<ConnGuard<'_>, Error> {
+        let Some(id) = transaction_id else {
+            return Ok(ConnGuard::Shared(self.conn.lock().await));
+        };
+
+        let slot = self
+            .transactions
+            .lock()
+            .await
+            .get(&id)
+            .cloned()
+            .ok_or(Error::TransactionNotFound(id))?;
+        let guard = slot.lock_owned().await;
+        if guard.is_none() {
+            return Err(Error::TransactionNotFound(id));
+        }
+        Ok(ConnGuard::Transaction(guard))
+    }
+
+    /// Lock a read connection: an idle one if there is one, otherwise the next
+    /// in turn. `None` when there is no read pool.
+    async fn acquire_reader(&self) -> Option<OwnedMutexGuard<Connection>> {
+        if self.readers.is_empty() {
+            return None;
+        }
+        if let Some(reader) = self.readers.iter().find_map(|r| r.try_lock_owned()) {
+            return Some(reader);
+        }
+        let next = self.next_reader.fetch_add(1, Ordering::Relaxed) % self.readers.len();
+        Some(self.readers[next].clone().lock_owned().await)
+    }
+
     /// Execute a query that doesn't return rows
-    pub async fn execute(&self, query: &str, values: Vec<JsonValue>) -> Result<QueryResult, Error> {
-        let params = json_to_params(values);
-        let rows_affected = self.conn.execute(query, params).await?;
+    pub async fn execute(
+        &self,
+        query: &str,
+        values: QueryValues,
+        transaction_id: Option<u64>,
+    ) -> Result<QueryResult, Error> {
+        let params = json_to_params(values, self.encodings)?;
+        let conn = self.acquire(transaction_id).await?;
+        // `last_insert_rowid` is per connection, so the lock must be held until
+        // it has been read — otherwise a concurrent execute on the same
+        // connection could report this statement's id, or this one its id.
+        let rows_affected = conn.execute(query, params).await?;
+        let last_insert_id = conn.last_insert_rowid();
+        drop(conn);
 
         Ok(QueryResult {
             rows_affected,
-            last_insert_id: self.conn.last_insert_rowid(),
+            last_insert_id,
         })
     }
 
@@ -201,96 +388,2450 @@ impl DbConnection {
     pub async fn select(
         &self,
         query: &str,
-        values: Vec<JsonValue>,
-    ) -> Result<Vec<IndexMap<String, JsonValue>>, Error> {
-        let params = json_to_params(values);
-        let mut rows = self.conn.query(query, params).await?;
-
-        let mut results = Vec::new();
+        values: QueryValues,
+        transaction_id: Option<u64>,
+        options: &QueryOptions,
+    ) -> Result<SelectResult, Error> {
+        let encodings = self.encodings.with_overrides(options);
+        let params = json_to_params(values, encodings)?;
+        self.read(query, params, transaction_id, |stmt, params| async move {
+            select_on(stmt, params, options, encodings).await
+        })
+        .await
+    }
 
-        while let Some(row) = rows.next().await? {
-            let mut map = IndexMap::new();
-            let column_count = row.column_count();
+    /// Like `select`, but returns the rows as a MessagePack-encoded columnar
+    /// result (see `binary_select_on`) instead of JSON values.
+    pub async fn select_binary(
+        &self,
+        query: &str,
+        values: QueryValues,
+        transaction_id: Option<u64>,
+        options: &QueryOptions,
+    ) -> Result<Vec<u8>, Error> {
+        let encodings = self.encodings.with_overrides(options);
+        let params = json_to_params(values, encodings)?;
+        self.read(query, params, transaction_id, |stmt, params| async move {
+            binary_select_on(stmt, params, options).await
+        })
+        .await
+    }
 
-            for i in 0..column_count {
-                if let Some(column_name) = row.column_name(i) {
-                    let value = decode::to_json(&row, i)?;
-                    map.insert(column_name.to_string(), value);
+    /// Prepare `query` and run it with `run` on a read connection when outside
+    /// a transaction, falling back to the writer (or the transaction's
+    /// connection) otherwise, or when the statement can't run on a reader:
+    /// it names a table or schema only the writer has (see
+    /// `missing_on_reader`), or it writes (see `is_readonly_error`). Neither
+    /// lets anything run on the reader, so nothing runs twice.
+    async fn read<T, F, Fut>(
+        &self,
+        query: &str,
+        params: Params,
+        transaction_id: Option<u64>,
+        run: F,
+    ) -> Result<T, Error>
+    where
+        F: Fn(Statement, Params) -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        if transaction_id.is_none() {
+            if let Some(reader) = self.acquire_reader().await {
+                match reader.prepare(query).await {
+                    Ok(stmt) => match run(stmt, params.clone()).await {
+                        Err(e) if is_readonly_error(&e) => {}
+                        result => return result,
+                    },
+                    Err(e) if missing_on_reader(&e) => {}
+                    Err(e) => return Err(e.into()),
                 }
             }
-
-            results.push(map);
         }
 
-        Ok(results)
+        let conn = self.acquire(transaction_id).await?;
+        let stmt = conn.prepare(query).await?;
+        run(stmt, params).await
+    }
+
+    /// Run any statement on the writer and return its rows (if it produces
+    /// columns) together with the rows it changed. Unlike `execute` and
+    /// `select`, the caller doesn't need to know which kind it is: `WITH …
+    /// SELECT`, `INSERT … RETURNING` and `PRAGMA` all work.
+    pub async fn query(
+        &self,
+        query: &str,
+        values: QueryValues,
+        transaction_id: Option<u64>,
+        options: &QueryOptions,
+    ) -> Result<StatementResult, Error> {
+        let encodings = self.encodings.with_overrides(options);
+        let params = json_to_params(values, encodings)?;
+        let conn = self.acquire(transaction_id).await?;
+        let stmt = conn.prepare(query).await?;
+
+        let (rows, rows_affected) = if stmt.column_count() > 0 {
+            // `changes()` isn't reset by statements that don't write, so it
+            // only counts when the connection's total moved. The total can't
+            // be used itself, as it includes rows changed by triggers.
+            let changes_before = conn.total_changes();
+            let rows = stmt.query(params).await?;
+            let metadata = column_metadata(&stmt, &rows);
+            let decoder = result_decoder(options, encodings, &metadata);
+            let rows = collect_columnar(rows, metadata, &decoder).await?;
+            let rows_affected = if conn.total_changes() == changes_before {
+                0
+            } else {
+                conn.changes()
+            };
+            (rows, rows_affected)
+        } else {
+            let rows_affected = stmt.execute(params).await? as u64;
+            (ColumnarResult::default(), rows_affected)
+        };
+        let last_insert_id = conn.last_insert_rowid();
+        drop(conn);
+
+        Ok(StatementResult {
+            rows,
+            rows_affected,
+            last_insert_id,
+        })
     }
 
     /// Execute multiple SQL statements atomically inside a transaction.
     /// Statements must not contain bound parameters — use for DDL and bulk DML only.
     pub async fn batch(&self, queries: Vec<String>) -> Result<(), Error> {
-        self.conn.execute("BEGIN", Params::None).await?;
+        let conn = self.conn.lock().await;
+        conn.execute("BEGIN", Params::None).await?;
         for query in &queries {
-            if let Err(e) = self.conn.execute(query.as_str(), Params::None).await {
-                let _ = self.conn.execute("ROLLBACK", Params::None).await;
+            if let Err(e) = conn.execute(query.as_str(), Params::None).await {
+                let _ = conn.execute("ROLLBACK", Params::None).await;
                 return Err(Error::Libsql(e));
             }
         }
-        if let Err(e) = self.conn.execute("COMMIT", Params::None).await {
-            let _ = self.conn.execute("ROLLBACK", Params::None).await;
+        if let Err(e) = conn.execute("COMMIT", Params::None).await {
+            let _ = conn.execute("ROLLBACK", Params::None).await;
             return Err(Error::Libsql(e));
         }
         Ok(())
     }
 
-    pub async fn close(&self) {
-        self.conn.reset().await;
-    }
-}
+    /// Execute parameterized statements atomically and return one result per
+    /// statement, in order. Statements that return columns yield their rows.
+    ///
+    /// Inside an interactive transaction the batch runs in a savepoint, so a
+    /// failure only undoes the batch's own statements.
+    pub async fn batch_params(
+        &self,
+        statements: Vec<BatchStatement>,
+        transaction_id: Option<u64>,
+    ) -> Result<Vec<BatchResult>, Error> {
+        let conn = self.acquire(transaction_id).await?;
+        let savepoint = transaction_id.map(|_| "batch_params");
+
+        let begin = if savepoint.is_some() {
+            "SAVEPOINT batch_params"
+        } else {
+            "BEGIN"
+        };
+        conn.execute(begin, Params::None).await?;
+
+        let mut results = Vec::with_capacity(statements.len());
+        for statement in statements {
+            match run_statement(&conn, statement, self.encodings).await {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    rollback_batch(&conn, savepoint).await;
+                    return Err(e);
+                }
+            }
+        }
 
-/// Convert JSON values to libsql params
-fn json_to_params(values: Vec<JsonValue>) -> Params {
-    if values.is_empty() {
-        return Params::None;
+        let commit = if savepoint.is_some() {
+            "RELEASE batch_params"
+        } else {
+            "COMMIT"
+        };
+        if let Err(e) = conn.execute(commit, Params::None).await {
+            rollback_batch(&conn, savepoint).await;
+            return Err(Error::Libsql(e));
+        }
+        Ok(results)
     }
 
-    let params: Vec<Value> = values.into_iter().map(json_to_libsql_value).collect();
-    Params::Positional(params)
-}
+    /// Run every statement of an SQL script in order. Statements are split
+    /// the way SQLite itself would (see `script::split_statements`), so string
+    /// literals, comments and trigger bodies may contain `;`.
+    ///
+    /// With `atomic` the script runs in a transaction — a savepoint inside an
+    /// interactive transaction — and a failure undoes all of it; otherwise the
+    /// statements before the failing one stay applied. Either way the error
+    /// names the failing statement's index and offset.
+    pub async fn execute_script(
+        &self,
+        script: &str,
+        transaction_id: Option<u64>,
+        atomic: bool,
+    ) -> Result<ScriptResult, Error> {
+        let conn = self.acquire(transaction_id).await?;
+        let savepoint = transaction_id.map(|_| "execute_script");
+
+        if atomic {
+            let begin = if savepoint.is_some() {
+                "SAVEPOINT execute_script"
+            } else {
+                "BEGIN"
+            };
+            conn.execute(begin, Params::None).await?;
+        }
+
+        let result = match run_script(&conn, script).await {
+            Ok(result) => result,
+            Err(e) => {
+                if atomic {
+                    rollback_batch(&conn, savepoint).await;
+                }
+                return Err(e);
+            }
+        };
 
-fn json_to_libsql_value(v: JsonValue) -> Value {
-    match v {
-        JsonValue::Null => Value::Null,
-        JsonValue::Bool(b) => Value::Integer(if b { 1 } else { 0 }),
-        JsonValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                Value::Integer(i)
-            } else if let Some(f) = n.as_f64() {
-                Value::Real(f)
+        if atomic {
+            let commit = if savepoint.is_some() {
+                "RELEASE execute_script"
             } else {
-                Value::Null
+                "COMMIT"
+            };
+            if let Err(e) = conn.execute(commit, Params::None).await {
+                rollback_batch(&conn, savepoint).await;
+                return Err(Error::Libsql(e));
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Apply the migrations that haven't run on this database yet, on the
+    /// writer, up to the version `migrate_to` last pinned it at. Returns the
+    /// names of the migrations applied.
+    pub async fn migrate(&self, migrations: &[Migration]) -> Result<Vec<String>, Error> {
+        let conn = self.acquire(None).await?;
+        crate::migrate::apply(&conn, migrations).await
+    }
+
+    /// Revert or apply `migrations` until the database is at `version`. A
+    /// version below the newest migration sticks: `migrate` won't go past it.
+    pub async fn migrate_to(
+        &self,
+        migrations: &[Migration],
+        version: u64,
+    ) -> Result<MigrateToResult, Error> {
+        let conn = self.acquire(None).await?;
+        crate::migrate::migrate_to(&conn, migrations, version).await
+    }
+
+    /// Dry-run migrating to `version` (or applying every pending migration)
+    /// and report what would change, leaving the database untouched
+    pub async fn migration_plan(
+        &self,
+        migrations: &[Migration],
+        version: Option<u64>,
+    ) -> Result<MigrationPlan, Error> {
+        let conn = self.acquire(None).await?;
+        crate::migrate::plan(&conn, migrations, version).await
+    }
+
+    /// Report which of `migrations` are applied, pending or modified
+    pub async fn migration_status(
+        &self,
+        migrations: &[Migration],
+    ) -> Result<Vec<MigrationStatus>, Error> {
+        let conn = self.acquire(None).await?;
+        crate::migrate::status(&conn, migrations).await
+    }
+
+    // ── dumps ────────────────────────────────────────────────────────────────
+
+    /// Write a plain-SQL dump of the database to `path`. The dump is read in
+    /// a single read transaction, so it is a consistent snapshot; a read
+    /// connection is used when there is one, so writes aren't blocked.
+    pub async fn export_sql(&self, path: &Path) -> Result<DumpResult, Error> {
+        let reader = self.acquire_reader().await;
+        let writer;
+        let conn: &Connection = match &reader {
+            Some(reader) => reader,
+            None => {
+                writer = self.acquire(None).await?;
+                &writer
+            }
+        };
+
+        // Like `export_query`, the dump replaces `path` only once it is complete
+        let partial = partial_path(path);
+        let _ = std::fs::remove_file(&partial);
+        let mut file = BufWriter::new(File::create(&partial)?);
+        let result = async {
+            conn.execute("BEGIN", Params::None).await?;
+            let result = crate::dump::export(conn, &mut file).await;
+            let _ = conn.execute("COMMIT", Params::None).await;
+            result
+        }
+        .await;
+        drop(file);
+
+        match result {
+            Ok(dump) => {
+                std::fs::rename(&partial, path)?;
+                Ok(dump)
             }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                Err(e)
+            }
+        }
+    }
+
+    /// Restore a dump written by `export_sql` (or the `sqlite3` shell's
+    /// `.dump`) from `path` into this database, which must be empty
+    pub async fn import_sql(&self, path: &Path) -> Result<ScriptResult, Error> {
+        let file = BufReader::new(File::open(path)?);
+        let conn = self.acquire(None).await?;
+        crate::dump::import(&conn, file).await
+    }
+
+    /// Write a consistent, compacted copy of the database to `target`,
+    /// reporting its progress to `send`. With a read pool it is made on a
+    /// connection of its own, so writers aren't blocked; otherwise writes wait. The
+    /// copy is encrypted with `encryption` if given, otherwise like the
+    /// database, and passes an integrity check before it replaces any
+    /// existing `target`. Returns its size in bytes.
+    pub async fn backup<F>(
+        &self,
+        target: &Path,
+        encryption: Option<EncryptionConfig>,
+        send: F,
+    ) -> Result<u64, Error>
+    where
+        F: Fn(BackupEvent) + Send + Sync + 'static,
+    {
+        let Some(file) = &self.file else {
+            return Err(Error::OperationNotSupported(
+                "backup requires a local database file".into(),
+            ));
+        };
+        if is_database_file(target, file) {
+            return Err(Error::InvalidPath(
+                "a backup can't overwrite its own database".into(),
+            ));
         }
-        JsonValue::String(s) => Value::Text(s),
-        JsonValue::Array(ref arr) => {
-            if arr.iter().all(|v| v.is_number()) {
-                let bytes: Vec<u8> = arr
-                    .iter()
-                    .filter_map(|v| v.as_u64().map(|n| n as u8))
-                    .collect();
-                Value::Blob(bytes)
+        let send: Arc<dyn Fn(BackupEvent) + Send + Sync> = Arc::new(send);
+        let partial = partial_path(target);
+        let _ = std::fs::remove_file(&partial);
+
+        let result = async {
+            let key = encryption.as_ref().map(|config| config.key.as_slice());
+            if self.readers.is_empty() {
+                let conn = self.acquire(None).await?;
+                crate::backup::vacuum_into(&conn, &partial, key, send.clone()).await?;
             } else {
-                Value::Text(v.to_string())
+                // In WAL mode a connection of its own copies a snapshot without
+                // holding up the writer. A pooled reader won't do: it is
+                // query_only, which `VACUUM INTO` counts as a write, and if
+                // turning that back on failed, the pool would keep a reader
+                // that can write.
+                let conn = self.db.connect()?;
+                crate::backup::vacuum_into(&conn, &partial, key, send.clone()).await?;
+            }
+
+            send(BackupEvent::Verifying);
+            let copy =
+                Self::open_local(partial.clone(), encryption.or(self.encryption.clone())).await?;
+            crate::backup::check_integrity(&copy.connect()?).await?;
+            drop(copy);
+
+            std::fs::rename(&partial, target)?;
+            Ok(std::fs::metadata(target)?.len())
+        }
+        .await;
+
+        if result.is_err() {
+            let _ = std::fs::remove_file(&partial);
+        }
+        result
+    }
+
+    /// Check a backup and copy it next to the database file, ready to be
+    /// swapped in by `DbInstances::restore`. `encryption` is the backup's key
+    /// if it differs from the database's; the copy is re-encrypted with the
+    /// database's key so the restored file opens like the original.
+    async fn stage_restore(
+        &self,
+        backup: &Path,
+        encryption: Option<EncryptionConfig>,
+    ) -> Result<PathBuf, Error> {
+        let file = match &self.file {
+            Some(file) if !self.replica => file,
+            _ => {
+                return Err(Error::OperationNotSupported(
+                    "restore requires a local database file".into(),
+                ))
+            }
+        };
+        if backup == file {
+            return Err(Error::InvalidPath(
+                "a database can't be restored from itself".into(),
+            ));
+        }
+        if self.encryption.is_none() && encryption.is_some() {
+            return Err(Error::OperationNotSupported(
+                "an encrypted backup can't be restored into an unencrypted database".into(),
+            ));
+        }
+        // Opening a missing file would create an empty database
+        std::fs::metadata(backup)?;
+
+        let key = match (&encryption, &self.encryption) {
+            (Some(backup_key), Some(key)) if backup_key.key != key.key => Some(key.key.as_slice()),
+            _ => None,
+        };
+        let source = Self::open_local(backup.to_path_buf(), encryption.or(self.encryption.clone()))
+            .await?
+            .connect()?;
+        crate::backup::check_integrity(&source).await?;
+
+        let mut staged = file.as_os_str().to_owned();
+        staged.push(".restore");
+        let staged = PathBuf::from(staged);
+        let _ = std::fs::remove_file(&staged);
+        if let Err(e) = crate::backup::vacuum_into(&source, &staged, key, Arc::new(|_| {})).await {
+            let _ = std::fs::remove_file(&staged);
+            return Err(e);
+        }
+        Ok(staged)
+    }
+
+    /// Wait until no statement is running on the connection — nothing else
+    /// holds it — then close it and drop it once cancelled streams have let go
+    /// of their cursors, closing every handle on the database file.
+    ///
+    /// Open transactions are rolled back and streams cancelled only once the
+    /// connection is idle: if statements are still running after `timeout`,
+    /// it is given back untouched.
+    async fn close_exclusive(self: Arc<Self>, timeout: Duration) -> Result<(), Arc<Self>> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while Arc::strong_count(&self) > 1 {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(self);
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // A cancelled stream lets go within the chunk it is reading
+        self.close().await;
+        while Arc::strong_count(&self.conn) > 1
+            || self.readers.iter().any(|r| Arc::strong_count(r) > 1)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
+
+    /// Import the records of a CSV or NDJSON file into `table`, in one
+    /// transaction with a single prepared `INSERT`. Progress and skipped
+    /// records are reported to `send`. NDJSON values bind like query values.
+    pub async fn import_file<F>(
+        &self,
+        path: &Path,
+        table: &str,
+        options: &ImportOptions,
+        send: F,
+    ) -> Result<ImportResult, Error>
+    where
+        F: FnMut(ImportEvent),
+    {
+        let path = path.to_path_buf();
+        let (file, total_bytes) = tokio::task::spawn_blocking(move || {
+            let file = File::open(path)?;
+            let total_bytes = file.metadata()?.len();
+            Ok::<_, std::io::Error>((file, total_bytes))
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        let conn = self.acquire(None).await?;
+        crate::import::import(
+            &conn,
+            BufReader::new(file),
+            total_bytes,
+            table,
+            options,
+            self.encodings,
+            send,
+        )
+        .await
+    }
+
+    /// Run a query and write its rows to `path` as CSV or NDJSON while they
+    /// are read, so the result is never held in memory. Integers are written
+    /// exactly; non-finite reals follow the connection's `NonFinitePolicy`.
+    /// Returns the number of rows written.
+    pub async fn export_query(
+        &self,
+        query: &str,
+        values: QueryValues,
+        path: &Path,
+        options: &ExportOptions,
+    ) -> Result<u64, Error> {
+        let encodings = Encodings {
+            blob: options.blob_encoding.unwrap_or(BlobEncoding::Base64),
+            integer: IntegerEncoding::Number,
+            ..self.encodings
+        };
+        let params = json_to_params(values, encodings)?;
+
+        // Rows go to a file of our own, which replaces `path` only once every
+        // row has been written.
+        let partial = partial_path(path);
+        let _ = std::fs::remove_file(&partial);
+        let file = File::create(&partial)?;
+        let result = self
+            .read(query, params, None, |stmt, params| {
+                let mut file = &file;
+                async move {
+                    // A statement retried on the writer starts the file over
+                    file.set_len(0)?;
+                    file.seek(SeekFrom::Start(0))?;
+                    let rows = stmt.query(params).await?;
+                    let mut out = BufWriter::new(file);
+                    let decoder = Decoder::new(encodings).bare_blobs();
+                    crate::export::write_rows(rows, &decoder, options, &mut out).await
+                }
+            })
+            .await;
+        drop(file);
+
+        match result {
+            Ok(rows) => {
+                std::fs::rename(&partial, path)?;
+                Ok(rows)
+            }
+            Err(e) => {
+                let _ = std::fs::remove_file(&partial);
+                Err(e)
+            }
+        }
+    }
+
+    /// Describe the tables, views, indexes and triggers of every schema,
+    /// including attached databases. Runs on the writer, since `ATTACH` is
+    /// per connection.
+    pub async fn introspect(&self, transaction_id: Option<u64>) -> Result<Vec<SchemaInfo>, Error> {
+        let conn = self.acquire(transaction_id).await?;
+        crate::introspect::introspect(&conn).await
+    }
+
+    // ── streaming ────────────────────────────────────────────────────────────
+
+    /// Run a read-only query and push its rows to `send` in chunks of up to
+    /// `chunk_size`, as the cursor yields them. Returns the stream id.
+    ///
+    /// At most `STREAM_WINDOW` chunks are sent ahead of `ack_stream` calls.
+    /// The cursor and its connection are released after the `Done` or `Error`
+    /// event, on `cancel_stream`, when `send` returns `false` (receiver gone),
+    /// or when the consumer stays silent for `STREAM_IDLE_TIMEOUT`; the last
+    /// two end with an `Error` event so the consumer isn't left waiting.
+    ///
+    /// Without a read pool the cursor is on the writer, which is only locked
+    /// while a chunk is read. The consumer can run statements between chunks,
+    /// with limits, since they share the cursor's connection: interactive
+    /// transactions are refused until the stream ends, and DDL on a streamed
+    /// table fails as locked. A read pool lifts both.
+    pub async fn select_stream<F>(
+        &self,
+        query: &str,
+        values: QueryValues,
+        chunk_size: usize,
+        mut send: F,
+    ) -> Result<u64, Error>
+    where
+        F: FnMut(StreamEvent) -> bool + Send + 'static,
+    {
+        let params = json_to_params(values, self.encodings)?;
+        let mut reader = self.acquire_reader().await;
+        let mut rows = None;
+        if let Some(conn) = &reader {
+            match conn.prepare(query).await {
+                Ok(stmt) => rows = Some(stmt.query(params.clone()).await?),
+                Err(e) if missing_on_reader(&e) => reader = None,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let mut rows = match rows {
+            Some(rows) => rows,
+            None => self.conn.lock().await.query(query, params).await?,
+        };
+        let writer = reader.is_none().then(|| self.conn.clone());
+        if writer.is_some() {
+            self.writer_streams.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let id = self.next_stream_id.fetch_add(1, Ordering::Relaxed);
+        let credits = Arc::new(Semaphore::new(STREAM_WINDOW));
+        self.streams.lock().await.insert(id, credits.clone());
+
+        let streams = self.streams.clone();
+        let writer_streams = self.writer_streams.clone();
+        let chunk_size = chunk_size.max(1);
+        let idle_timeout = self.stream_idle_timeout;
+        let decoder = Decoder::new(self.encodings);
+        tokio::spawn(async move {
+            let mut total_rows = 0;
+            loop {
+                match tokio::time::timeout(idle_timeout, credits.acquire()).await {
+                    Ok(Ok(permit)) => permit.forget(),
+                    Ok(Err(_)) => {
+                        send(StreamEvent::Error("the stream was cancelled".into()));
+                        break;
+                    }
+                    Err(_) => {
+                        send(StreamEvent::Error(
+                            "the stream timed out waiting for an acknowledgement".into(),
+                        ));
+                        break;
+                    }
+                }
+
+                let writer = match &writer {
+                    Some(writer) => Some(writer.lock().await),
+                    None => None,
+                };
+                let chunk = next_chunk(&mut rows, chunk_size, &decoder).await;
+                drop(writer);
+                let chunk = match chunk {
+                    Ok(chunk) => chunk,
+                    Err(e) => {
+                        send(StreamEvent::Error(e.to_string()));
+                        break;
+                    }
+                };
+                let exhausted = chunk.len() < chunk_size;
+                total_rows += chunk.len() as u64;
+
+                if !chunk.is_empty() && !send(StreamEvent::Rows(chunk)) {
+                    break;
+                }
+                if exhausted {
+                    send(StreamEvent::Done { total_rows });
+                    break;
+                }
+            }
+
+            streams.lock().await.remove(&id);
+            drop(rows);
+            drop(reader);
+            if writer.is_some() {
+                writer_streams.fetch_sub(1, Ordering::Relaxed);
+            }
+            drop(writer);
+        });
+
+        Ok(id)
+    }
+
+    /// Allow a stream to send one more chunk. Unknown ids are ignored: the
+    /// stream may simply have finished.
+    pub async fn ack_stream(&self, stream_id: u64) {
+        if let Some(credits) = self.streams.lock().await.get(&stream_id) {
+            credits.add_permits(1);
+        }
+    }
+
+    /// Stop a stream and drop its cursor. Unknown ids are ignored.
+    pub async fn cancel_stream(&self, stream_id: u64) {
+        if let Some(credits) = self.streams.lock().await.remove(&stream_id) {
+            credits.close();
+        }
+    }
+
+    // ── interactive transactions ─────────────────────────────────────────────
+
+    /// Begin an interactive transaction and return its id.
+    ///
+    /// The transaction takes exclusive ownership of the connection until it is
+    /// committed or rolled back; statements without the id queue behind it.
+    /// If neither happens within `timeout`, it is rolled back automatically so
+    /// an abandoned transaction can't hold the write lock forever.
+    ///
+    /// Fails while a `select_stream` reads from the writer: the stream would
+    /// wait for the transaction, and a consumer for the stream.
+    pub async fn begin_transaction(&self, timeout: Duration) -> Result<u64, Error> {
+        let conn = self.conn.clone().lock_owned().await;
+        if self.writer_streams.load(Ordering::Relaxed) > 0 {
+            return Err(Error::OperationNotSupported(
+                "a transaction can't begin while a stream reads from the writer — \
+                 finish or cancel the stream first, or enable the read pool"
+                    .into(),
+            ));
+        }
+        conn.execute("BEGIN IMMEDIATE", Params::None).await?;
+
+        let id = self.next_transaction_id.fetch_add(1, Ordering::Relaxed);
+        let slot: TransactionSlot = Arc::new(Mutex::new(Some(conn)));
+        self.transactions.lock().await.insert(id, slot);
+
+        let transactions = self.transactions.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+            // Already committed or rolled back if it's no longer registered
+            let _ = Self::finish_transaction(&transactions, id, "ROLLBACK").await;
+        });
+
+        Ok(id)
+    }
+
+    /// Commit an interactive transaction.
+    pub async fn commit(&self, transaction_id: u64) -> Result<(), Error> {
+        Self::finish_transaction(&self.transactions, transaction_id, "COMMIT").await
+    }
+
+    /// Roll back an interactive transaction.
+    pub async fn rollback(&self, transaction_id: u64) -> Result<(), Error> {
+        Self::finish_transaction(&self.transactions, transaction_id, "ROLLBACK").await
+    }
+
+    /// Unregister a transaction, wait for its in-flight statement (if any),
+    /// then end it and release the connection.
+    async fn finish_transaction(
+        transactions: &Transactions,
+        id: u64,
+        statement: &str,
+    ) -> Result<(), Error> {
+        let slot = transactions
+            .lock()
+            .await
+            .remove(&id)
+            .ok_or(Error::TransactionNotFound(id))?;
+        let conn = slot
+            .lock()
+            .await
+            .take()
+            .ok_or(Error::TransactionNotFound(id))?;
+
+        if let Err(e) = conn.execute(statement, Params::None).await {
+            // A failed COMMIT leaves the transaction open; never hand the
+            // connection back in that state.
+            if !conn.is_autocommit() {
+                let _ = conn.execute("ROLLBACK", Params::None).await;
             }
+            return Err(Error::Libsql(e));
+        }
+        Ok(())
+    }
+
+    pub async fn close(&self) {
+        for (_, credits) in self.streams.lock().await.drain() {
+            credits.close();
+        }
+        let ids: Vec<u64> = self.transactions.lock().await.keys().copied().collect();
+        for id in ids {
+            let _ = Self::finish_transaction(&self.transactions, id, "ROLLBACK").await;
+        }
+        self.conn.lock().await.reset().await;
+        for reader in &self.readers {
+            reader.lock().await.reset().await;
         }
-        JsonValue::Object(_) => Value::Text(v.to_string()),
     }
 }
 
-/// Database instances holder
-pub struct DbInstances(pub Arc<Mutex<HashMap<String, Arc<DbConnection>>>>);
+/// Run one statement of a parameterized batch, returning rows if it has columns
+async fn run_statement(
+    conn: &Connection,
+    statement: BatchStatement,
+    encodings: Encodings,
+) -> Result<BatchResult, Error> {
+    let params = json_to_params(statement.values, encodings)?;
+    let stmt = conn.prepare(&statement.query).await?;
 
-impl Default for DbInstances {
-    fn default() -> Self {
-        Self(Arc::new(Mutex::new(HashMap::new())))
+    if stmt.column_count() > 0 {
+        let rows = stmt.query(params).await?;
+        return Ok(BatchResult::Rows(
+            collect_rows(rows, &Decoder::new(encodings)).await?,
+        ));
+    }
+
+    let rows_affected = stmt.execute(params).await? as u64;
+    Ok(BatchResult::Execute(QueryResult {
+        rows_affected,
+        last_insert_id: conn.last_insert_rowid(),
+    }))
+}
+
+/// Resolve a file path for an export or import against `base_path`. Unlike
+/// database paths, absolute paths are only accepted inside `base_path` too.
+pub(crate) fn resolve_file_path(path: &str, base_path: &Path) -> Result<PathBuf, Error> {
+    let resolved = normalise(&base_path.join(path));
+    if path.is_empty() || !resolved.starts_with(normalise(base_path)) {
+        return Err(Error::InvalidPath(format!(
+            "'{path}' is outside the base directory"
+        )));
+    }
+    Ok(resolved)
+}
+
+/// The file a result is written to before it replaces `target`, so a failed
+/// write never touches an existing `target`
+fn partial_path(target: &Path) -> PathBuf {
+    let mut partial = target.as_os_str().to_owned();
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+/// Whether `path` is the database `file` or its `-wal`, `-shm` or `-journal`
+/// file, after resolving `.` and `..` in both
+fn is_database_file(path: &Path, file: &Path) -> bool {
+    let path = normalise(path);
+    let file = normalise(file);
+    ["", "-wal", "-shm", "-journal"].iter().any(|suffix| {
+        let mut sibling = file.as_os_str().to_owned();
+        sibling.push(suffix);
+        path.as_os_str() == sibling
+    })
+}
+
+/// Resolve `.` and `..` components lexically
+fn normalise(path: &Path) -> PathBuf {
+    path.components().fold(PathBuf::new(), |mut acc, c| {
+        match c {
+            Component::ParentDir => {
+                acc.pop();
+            }
+            Component::CurDir => {}
+            _ => acc.push(c),
+        }
+        acc
+    })
+}
+
+/// Whether a statement failed to prepare on a read connection because it
+/// names a `TEMP` table or an `ATTACH`ed database: those are per connection,
+/// so only the writer has them. Preparing runs nothing, so the writer may
+/// safely try again.
+fn missing_on_reader(e: &libsql::Error) -> bool {
+    let libsql::Error::SqliteFailure(code, message) = e else {
+        return false;
+    };
+    code & 0xff == SQLITE_ERROR
+        && (message.starts_with("no such table") || message.starts_with("unknown database"))
+}
+
+/// Whether a statement failed on a read connection because it writes (e.g.
+/// `INSERT … RETURNING`). Readers are query_only, which stops the statement
+/// at its first write, before it changes or returns anything.
+fn is_readonly_error(e: &Error) -> bool {
+    matches!(
+        e,
+        Error::Libsql(libsql::Error::SqliteFailure(code, _)) if code & 0xff == SQLITE_READONLY
+    )
+}
+
+/// Undo a batch: back to `savepoint` if it ran in one, otherwise the whole
+/// transaction
+async fn rollback_batch(conn: &Connection, savepoint: Option<&str>) {
+    if let Some(name) = savepoint {
+        let _ = conn
+            .execute(&format!("ROLLBACK TO {name}"), Params::None)
+            .await;
+        let _ = conn.execute(&format!("RELEASE {name}"), Params::None).await;
+    } else {
+        let _ = conn.execute("ROLLBACK", Params::None).await;
+    }
+}
+
+/// Run the statements of `script` in order, stopping at the first failure.
+/// Returns how many statements ran and the rows they changed themselves, as
+/// `execute` counts them: rows changed by triggers aren't included.
+pub(crate) async fn run_script(conn: &Connection, script: &str) -> Result<ScriptResult, Error> {
+    let statements = split_statements(script);
+    let mut rows_affected = 0;
+    for (index, statement) in statements.iter().enumerate() {
+        rows_affected += run_script_statement(conn, statement.sql)
+            .await
+            .map_err(|e| Error::ScriptStatement {
+                index,
+                offset: statement.offset,
+                line: script[..statement.offset].matches('\n').count() + 1,
+                source: Box::new(e),
+            })?;
+    }
+    Ok(ScriptResult {
+        statements: statements.len(),
+        rows_affected,
+    })
+}
+
+/// Run one statement of a script, draining any rows it returns. Returns the
+/// rows it changed.
+async fn run_script_statement(conn: &Connection, sql: &str) -> Result<u64, Error> {
+    // As in `query`, `changes()` is only this statement's when the total moved
+    let changes_before = conn.total_changes();
+    let stmt = conn.prepare(sql).await?;
+    if stmt.column_count() > 0 {
+        let mut rows = stmt.query(Params::None).await?;
+        while rows.next().await?.is_some() {}
+    } else {
+        stmt.execute(Params::None).await?;
+    }
+    Ok(if conn.total_changes() == changes_before {
+        0
+    } else {
+        conn.changes()
+    })
+}
+
+/// Run a query and decode its rows in the shape `options` asks for
+async fn select_on(
+    stmt: Statement,
+    params: Params,
+    options: &QueryOptions,
+    encodings: Encodings,
+) -> Result<SelectResult, Error> {
+    let rows = stmt.query(params).await?;
+    let metadata = column_metadata(&stmt, &rows);
+    let decoder = result_decoder(options, encodings, &metadata);
+
+    match options.format {
+        RowFormat::Objects => Ok(SelectResult::Objects(collect_rows(rows, &decoder).await?)),
+        RowFormat::Columnar => Ok(SelectResult::Columnar(
+            collect_columnar(rows, metadata, &decoder).await?,
+        )),
+    }
+}
+
+/// Run a query and encode its rows as a MessagePack map with the same shape
+/// as `ColumnarResult`: `columns`, `metadata` and `rows`. Values are written
+/// from the libsql row directly, so blobs stay binary and integers keep all
+/// 64 bits. `options.format` is ignored.
+async fn binary_select_on(
+    stmt: Statement,
+    params: Params,
+    options: &QueryOptions,
+) -> Result<Vec<u8>, Error> {
+    let mut rows = stmt.query(params).await?;
+    let metadata = column_metadata(&stmt, &rows);
+    let mut decoder = Decoder::default();
+    if options.typed {
+        decoder = decoder.typed(&metadata);
+    }
+    let column_count = rows.column_count();
+
+    // The row count is only known at the end, so rows go to their own buffer
+    let mut body = Encoder::new();
+    let mut row_count = 0;
+    while let Some(row) = rows.next().await? {
+        body.array(column_count as usize);
+        for i in 0..column_count {
+            decoder.to_msgpack(&row, i, &mut body)?;
+        }
+        row_count += 1;
+    }
+
+    let mut out = Encoder::new();
+    out.map(3);
+    out.str("columns");
+    out.array(metadata.len());
+    for column in &metadata {
+        out.str(&column.name);
+    }
+    out.str("metadata");
+    out.array(metadata.len());
+    for column in &metadata {
+        out.map(5);
+        out.str("name");
+        out.str(&column.name);
+        out.str("declType");
+        out.opt_str(column.decl_type.as_deref());
+        out.str("table");
+        out.opt_str(column.table.as_deref());
+        out.str("column");
+        out.opt_str(column.column.as_deref());
+        out.str("database");
+        out.opt_str(column.database.as_deref());
+    }
+    out.str("rows");
+    out.array(row_count);
+    out.raw(&body.into_bytes());
+    Ok(out.into_bytes())
+}
+
+/// The decoder for a result set with columns `metadata`, as `options` ask
+fn result_decoder(
+    options: &QueryOptions,
+    encodings: Encodings,
+    metadata: &[ColumnMetadata],
+) -> Decoder {
+    let mut decoder = Decoder::new(encodings);
+    if options.typed {
+        decoder = decoder.typed(metadata);
+    }
+    if !options.int64_columns.is_empty() {
+        decoder = decoder.int64_columns(metadata, &options.int64_columns);
+    }
+    decoder
+}
+
+/// Declared type and origin of each result column. Remote statements only
+/// know their column names, so the rest is left empty for them.
+fn column_metadata(stmt: &Statement, rows: &Rows) -> Vec<ColumnMetadata> {
+    let columns = stmt.columns();
+    if columns.len() == rows.column_count() as usize {
+        return columns
+            .iter()
+            .map(|c| ColumnMetadata {
+                name: c.name().to_string(),
+                decl_type: c.decl_type().map(str::to_string),
+                table: c.table_name().map(str::to_string),
+                column: c.origin_name().map(str::to_string),
+                database: c.database_name().map(str::to_string),
+            })
+            .collect();
+    }
+
+    (0..rows.column_count())
+        .map(|i| ColumnMetadata {
+            name: rows.column_name(i).unwrap_or_default().to_string(),
+            decl_type: None,
+            table: None,
+            column: None,
+            database: None,
+        })
+        .collect()
+}
+
+/// Decode every remaining row into an array of values in column order. Unlike
+/// maps, this keeps columns that share a name (e.g. `a.id` and `b.id`).
+async fn collect_columnar(
+    mut rows: Rows,
+    metadata: Vec<ColumnMetadata>,
+    decoder: &Decoder,
+) -> Result<ColumnarResult, Error> {
+    let column_count = rows.column_count();
+
+    let mut results = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let values = (0..column_count)
+            .map(|i| decoder.to_json(&row, i))
+            .collect::<Result<_, _>>()?;
+        results.push(values);
+    }
+
+    Ok(ColumnarResult {
+        columns: metadata.iter().map(|c| c.name.clone()).collect(),
+        metadata,
+        rows: results,
+    })
+}
+
+/// Decode every remaining row into a column-name → value map
+async fn collect_rows(
+    mut rows: Rows,
+    decoder: &Decoder,
+) -> Result<Vec<IndexMap<String, JsonValue>>, Error> {
+    let mut results = Vec::new();
+
+    while let Some(row) = rows.next().await? {
+        results.push(row_to_map(&row, decoder)?);
+    }
+
+    Ok(results)
+}
+
+/// Decode up to `size` rows; fewer means the cursor is exhausted
+async fn next_chunk(
+    rows: &mut Rows,
+    size: usize,
+    decoder: &Decoder,
+) -> Result<Vec<IndexMap<String, JsonValue>>, Error> {
+    // `size` comes from the caller, so don't reserve more than a default chunk
+    let mut chunk = Vec::with_capacity(size.min(DEFAULT_STREAM_CHUNK_SIZE));
+
+    while chunk.len() < size {
+        match rows.next().await? {
+            Some(row) => chunk.push(row_to_map(&row, decoder)?),
+            None => break,
+        }
+    }
+
+    Ok(chunk)
+}
+
+fn row_to_map(row: &Row, decoder: &Decoder) -> Result<IndexMap<String, JsonValue>, Error> {
+    let mut map = IndexMap::new();
+    let column_count = row.column_count();
+
+    for i in 0..column_count {
+        if let Some(column_name) = row.column_name(i) {
+            let value = decoder.to_json(row, i)?;
+            map.insert(column_name.to_string(), value);
+        }
+    }
+
+    Ok(map)
+}
+
+/// Databases being restored, keyed like the instance map. Each gate is held
+/// by `DbInstances::restore` until the database is back in the map.
+type Restoring = Mutex<HashMap<String, Arc<Mutex<()>>>>;
+
+/// Database instances holder
+pub struct DbInstances(
+    pub Arc<Mutex<HashMap<String, Arc<DbConnection>>>>,
+    Restoring,
+);
+
+impl Default for DbInstances {
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(HashMap::new())), Mutex::default())
+    }
+}
+
+impl DbInstances {
+    /// Clone the connection for `db` out of the map. The lock is released on
+    /// return so awaiting a query doesn't block other operations. While `db`
+    /// is being restored, this waits until it is back.
+    pub(crate) async fn get(&self, db: &str) -> Result<Arc<DbConnection>, Error> {
+        loop {
+            let gate = {
+                let instances = self.0.lock().await;
+                if let Some(conn) = instances.get(db) {
+                    return Ok(conn.clone());
+                }
+                self.1.lock().await.get(db).cloned()
+            };
+            match gate {
+                Some(gate) => drop(gate.lock().await),
+                None => return Err(Error::DatabaseNotLoaded(db.to_string())),
+            }
+        }
+    }
+
+    /// Fail if `path` is the file of a loaded database, or its `-wal`, `-shm`
+    /// or `-journal` file, so an export or backup can't overwrite a live
+    /// database.
+    pub(crate) async fn check_not_live(&self, path: &Path) -> Result<(), Error> {
+        for conn in self.0.lock().await.values() {
+            let Some(file) = &conn.file else { continue };
+            if is_database_file(path, file) {
+                return Err(Error::InvalidPath(format!(
+                    "'{}' belongs to a loaded database",
+                    path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the database `db` with the backup at `backup` and reopen it
+    /// with the same options, applying `migrations` as `load` does.
+    ///
+    /// The backup is checked and staged next to the database file first.
+    /// Then the connection is taken out of the map — `get` waits for it to
+    /// come back, so no new statement starts, while other databases carry on
+    /// — and in-flight statements are waited for. If any are still running
+    /// after `RESTORE_TIMEOUT` (e.g. one queued behind a transaction whose
+    /// owner now waits on `get`), the restore fails with `DatabaseBusy` and
+    /// the connection goes back as it was. Only then is it closed: open
+    /// transactions are rolled back and streams cancelled. Finally the stale
+    /// `-wal`/`-shm` files are removed and the staged file is renamed over
+    /// the database. Other windows keep using `db` and see the restored data.
+    /// If reopening fails, `db` is left closed and can be loaded again.
+    ///
+    /// The swap is not crash-atomic: a crash between removing the `-wal` file
+    /// and the rename leaves the old database without it.
+    pub(crate) async fn restore(
+        &self,
+        db: &str,
+        backup: &Path,
+        encryption: Option<EncryptionConfig>,
+        migrations: &[Migration],
+    ) -> Result<(), Error> {
+        let staged = self
+            .get(db)
+            .await?
+            .stage_restore(backup, encryption)
+            .await?;
+
+        let gate = Arc::new(Mutex::new(()));
+        let restoring = gate.clone().lock_owned().await;
+        let conn = {
+            let mut instances = self.0.lock().await;
+            let Some(conn) = instances.remove(db) else {
+                let _ = std::fs::remove_file(&staged);
+                return Err(Error::DatabaseNotLoaded(db.to_string()));
+            };
+            self.1.lock().await.insert(db.to_string(), gate);
+            conn
+        };
+
+        let file = conn.file.clone().unwrap_or_default();
+        let encryption = conn.encryption.clone();
+        let read_pool_size = conn.readers.len();
+        let encodings = conn.encodings;
+        let (conn, result) = match conn.close_exclusive(RESTORE_TIMEOUT).await {
+            // Still in use: put it back as it was
+            Err(conn) => {
+                let _ = std::fs::remove_file(&staged);
+                (Some(conn), Err(Error::DatabaseBusy(db.to_string())))
+            }
+            Ok(()) => {
+                let conn = Self::swap_in(
+                    &staged,
+                    &file,
+                    encryption,
+                    read_pool_size,
+                    encodings,
+                    migrations,
+                );
+                match conn.await {
+                    Ok(conn) => (Some(Arc::new(conn)), Ok(())),
+                    Err(e) => (None, Err(e)),
+                }
+            }
+        };
+
+        let mut instances = self.0.lock().await;
+        if let Some(conn) = conn {
+            instances.insert(db.to_string(), conn);
+        }
+        self.1.lock().await.remove(db);
+        drop(instances);
+        drop(restoring);
+        result
+    }
+
+    /// Rename `staged` over the closed database `file`, whose stale `-wal`,
+    /// `-shm` and `-journal` files are removed first, then reopen it with the
+    /// database's encryption, read pool size and encodings and apply
+    /// `migrations`
+    async fn swap_in(
+        staged: &Path,
+        file: &Path,
+        encryption: Option<EncryptionConfig>,
+        read_pool_size: usize,
+        encodings: Encodings,
+        migrations: &[Migration],
+    ) -> Result<DbConnection, Error> {
+        let swapped = (|| {
+            for suffix in ["-wal", "-shm", "-journal"] {
+                let mut sibling = file.as_os_str().to_owned();
+                sibling.push(suffix);
+                match std::fs::remove_file(sibling) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+            std::fs::rename(staged, file)
+        })();
+        if let Err(e) = swapped {
+            let _ = std::fs::remove_file(staged);
+            return Err(e.into());
+        }
+
+        // `file` is absolute, so no base path is needed to resolve it
+        let conn = DbConnection::connect(
+            &file.to_string_lossy(),
+            encryption,
+            PathBuf::new(),
+            None,
+            None,
+            read_pool_size,
+            encodings,
+        )
+        .await?;
+        if !migrations.is_empty() {
+            conn.migrate(migrations).await?;
+        }
+        Ok(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block_on, test_dir};
+    use serde_json::json;
+
+    async fn open(dir: &Path, read_pool_size: usize) -> DbConnection {
+        DbConnection::connect(
+            "sqlite:test.db",
+            None,
+            dir.to_path_buf(),
+            None,
+            None,
+            read_pool_size,
+            Encodings::default(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[test]
+    fn concurrent_executes_report_their_own_last_insert_id() {
+        block_on(async {
+            let dir = test_dir("last-insert-id");
+            let conn = Arc::new(open(&dir, 2).await);
+            conn.execute(
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT NOT NULL)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+            let tasks: Vec<_> = (0..200)
+                .map(|i| {
+                    let conn = conn.clone();
+                    tokio::spawn(async move {
+                        let label = format!("item-{i}");
+                        let result = conn
+                            .execute(
+                                "INSERT INTO items (label) VALUES (?)",
+                                QueryValues::Positional(vec![json!(label)]),
+                                None,
+                            )
+                            .await
+                            .unwrap();
+                        (label, result.last_insert_id)
+                    })
+                })
+                .collect();
+
+            let mut ids = std::collections::HashSet::new();
+            for task in tasks {
+                let (label, id) = task.await.unwrap();
+                assert!(ids.insert(id), "id {id} reported twice");
+
+                let rows = conn
+                    .select(
+                        "SELECT label FROM items WHERE id = ?",
+                        QueryValues::Positional(vec![json!(id)]),
+                        None,
+                        &QueryOptions::default(),
+                    )
+                    .await
+                    .unwrap();
+                assert_eq!(json!(rows), json!([{ "label": label }]));
+            }
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn restore_swaps_in_a_backup_and_reopens() {
+        block_on(async {
+            let dir = test_dir("restore");
+            let instances = DbInstances::default();
+            instances
+                .0
+                .lock()
+                .await
+                .insert("sqlite:test.db".into(), Arc::new(open(&dir, 2).await));
+            let conn = instances.get("sqlite:test.db").await.unwrap();
+            for sql in [
+                "CREATE TABLE items (label TEXT)",
+                "INSERT INTO items VALUES ('kept')",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+            conn.backup(&dir.join("backup.db"), None, |_| {})
+                .await
+                .unwrap();
+            conn.execute(
+                "INSERT INTO items VALUES ('lost')",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+            drop(conn);
+
+            instances
+                .restore("sqlite:test.db", &dir.join("backup.db"), None, &[])
+                .await
+                .unwrap();
+
+            let conn = instances.get("sqlite:test.db").await.unwrap();
+            let rows = conn
+                .select(
+                    "SELECT label FROM items",
+                    QueryValues::default(),
+                    None,
+                    &QueryOptions::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(json!(rows), json!([{ "label": "kept" }]));
+            assert!(!dir.join("test.db.restore").exists());
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn a_busy_restore_leaves_transactions_and_streams_alone() {
+        block_on(async {
+            let dir = test_dir("restore-busy");
+            let conn = Arc::new(open(&dir, 2).await);
+            conn.execute(
+                "CREATE TABLE t AS WITH RECURSIVE n(x) AS \
+                 (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 10) SELECT x FROM n",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            conn.execute(
+                "INSERT INTO t VALUES (11)",
+                QueryValues::default(),
+                Some(id),
+            )
+            .await
+            .unwrap();
+            let (stream_id, mut events) = stream(&conn, "SELECT x FROM t", 1).await;
+            assert!(matches!(events.recv().await, Some(StreamEvent::Rows(_))));
+
+            // A statement still running (here: its handle) makes the restore
+            // give up before anything is rolled back or cancelled
+            let in_flight = conn.clone();
+            let conn = conn
+                .close_exclusive(Duration::from_millis(50))
+                .await
+                .unwrap_err();
+            drop(in_flight);
+
+            conn.commit(id).await.unwrap();
+            assert_eq!(count(&conn, None).await, 11);
+            conn.ack_stream(stream_id).await;
+            assert!(matches!(events.recv().await, Some(StreamEvent::Rows(_))));
+
+            // Once idle, closing ends the stream
+            conn.close_exclusive(Duration::from_millis(50))
+                .await
+                .map_err(|_| "still busy")
+                .unwrap();
+            while let Some(event) = events.recv().await {
+                assert!(!matches!(event, StreamEvent::Done { .. }));
+            }
+
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn failed_export_leaves_an_existing_file_alone() {
+        block_on(async {
+            let dir = test_dir("export-query");
+            let instances = DbInstances::default();
+            instances
+                .0
+                .lock()
+                .await
+                .insert("sqlite:test.db".into(), Arc::new(open(&dir, 1).await));
+            let conn = instances.get("sqlite:test.db").await.unwrap();
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+            conn.execute(
+                "INSERT INTO t VALUES (1), (2)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+            let target = dir.join("out.csv");
+            std::fs::write(&target, "previous").unwrap();
+            let options = ExportOptions::default();
+            let missing = "SELECT * FROM missing";
+            assert!(conn
+                .export_query(missing, QueryValues::default(), &target, &options)
+                .await
+                .is_err());
+            assert_eq!(std::fs::read_to_string(&target).unwrap(), "previous");
+            assert!(!dir.join("out.csv.partial").exists());
+
+            let count = conn
+                .export_query("SELECT x FROM t", QueryValues::default(), &target, &options)
+                .await
+                .unwrap();
+            assert_eq!(count, 2);
+            assert_eq!(std::fs::read_to_string(&target).unwrap(), "x\r\n1\r\n2\r\n");
+
+            instances.check_not_live(&target).await.unwrap();
+            for live in ["test.db", "test.db-wal", "test.db-shm", "sub/../test.db"] {
+                assert!(instances.check_not_live(&dir.join(live)).await.is_err());
+            }
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn backups_never_overwrite_a_loaded_database() {
+        block_on(async {
+            let dir = test_dir("backup-live");
+            let instances = DbInstances::default();
+            let other = DbConnection::connect(
+                "sqlite:other.db",
+                None,
+                dir.clone(),
+                None,
+                None,
+                0,
+                Encodings::default(),
+            )
+            .await
+            .unwrap();
+            {
+                let mut map = instances.0.lock().await;
+                map.insert("sqlite:test.db".into(), Arc::new(open(&dir, 0).await));
+                map.insert("sqlite:other.db".into(), Arc::new(other));
+            }
+            let conn = instances.get("sqlite:test.db").await.unwrap();
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            for own in ["test.db", "test.db-wal", "sub/../test.db-shm"] {
+                assert!(matches!(
+                    conn.backup(&dir.join(own), None, |_| {}).await,
+                    Err(Error::InvalidPath(_))
+                ));
+            }
+            assert!(!dir.join("test.db-wal").exists());
+            for live in ["other.db", "other.db-wal", "other.db-journal"] {
+                assert!(instances.check_not_live(&dir.join(live)).await.is_err());
+            }
+
+            let target = dir.join("backup.db");
+            instances.check_not_live(&target).await.unwrap();
+            conn.backup(&target, None, |_| {}).await.unwrap();
+
+            for (_, conn) in instances.0.lock().await.drain() {
+                conn.close().await;
+            }
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn migrating_down_survives_reopening() {
+        block_on(async {
+            let dir = test_dir("migrate-to");
+            let mut migrations = vec![
+                Migration::new(1, "0001_t.sql", "CREATE TABLE t (x);"),
+                Migration::new(2, "0002_u.sql", "CREATE TABLE u (y);").with_down("DROP TABLE u;"),
+            ];
+            // What `load` does
+            let load = |migrations: Vec<Migration>| {
+                let dir = dir.clone();
+                async move {
+                    let conn = open(&dir, 0).await;
+                    conn.migrate(&migrations).await.unwrap();
+                    conn
+                }
+            };
+
+            let conn = load(migrations.clone()).await;
+            let result = conn.migrate_to(&migrations, 1).await.unwrap();
+            assert_eq!(result.reverted, ["0002_u.sql"]);
+            conn.close().await;
+            drop(conn);
+
+            // Neither reopening nor a new release's migration undoes it
+            migrations.push(Migration::new(3, "0003_v.sql", "CREATE TABLE v (z);"));
+            let conn = load(migrations.clone()).await;
+            let tables = conn
+                .select(
+                    "SELECT name FROM sqlite_master WHERE name IN ('t', 'u', 'v')",
+                    QueryValues::default(),
+                    None,
+                    &QueryOptions::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(json!(tables), json!([{ "name": "t" }]));
+            let plan = conn.migration_plan(&migrations, None).await.unwrap();
+            assert!(plan.migrations.is_empty());
+
+            // Moving to the newest version lets `load` apply migrations again
+            let result = conn.migrate_to(&migrations, 3).await.unwrap();
+            assert_eq!(result.applied, ["0002_u.sql", "0003_v.sql"]);
+            conn.close().await;
+            drop(conn);
+            migrations.push(Migration::new(4, "0004_w.sql", "CREATE TABLE w (a);"));
+            let conn = load(migrations.clone()).await;
+            let status = conn.migration_status(&migrations).await.unwrap();
+            assert!(status
+                .iter()
+                .all(|m| m.state == crate::models::MigrationState::Applied));
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn export_sql_replaces_the_target_once_complete() {
+        block_on(async {
+            let dir = test_dir("export-sql");
+            let conn = open(&dir, 0).await;
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            let target = dir.join("dump.sql");
+            std::fs::write(&target, "previous").unwrap();
+            let dump = conn.export_sql(&target).await.unwrap();
+            assert_eq!(dump.tables, 1);
+            let sql = std::fs::read_to_string(&target).unwrap();
+            assert!(sql.contains("CREATE TABLE t (x)"));
+            assert!(!dir.join("dump.sql.partial").exists());
+
+            assert!(conn
+                .export_sql(&dir.join("missing/dump.sql"))
+                .await
+                .is_err());
+            assert!(!dir.join("missing").exists());
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    /// Start streaming `query` in chunks of `chunk_size`, collecting the
+    /// events into a channel
+    async fn stream(
+        conn: &DbConnection,
+        query: &str,
+        chunk_size: usize,
+    ) -> (u64, tokio::sync::mpsc::UnboundedReceiver<StreamEvent>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let id = conn
+            .select_stream(query, QueryValues::default(), chunk_size, move |event| {
+                tx.send(event).is_ok()
+            })
+            .await
+            .unwrap();
+        (id, rx)
+    }
+
+    #[test]
+    fn stream_waits_for_acknowledgements_and_ends_on_cancel() {
+        block_on(async {
+            let dir = test_dir("stream-ack");
+            let conn = open(&dir, 0).await;
+            conn.execute(
+                "CREATE TABLE t AS WITH RECURSIVE n(x) AS \
+                 (SELECT 1 UNION ALL SELECT x + 1 FROM n WHERE x < 10) SELECT x FROM n",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+            let idle = Duration::from_millis(100);
+
+            let (id, mut events) = stream(&conn, "SELECT x FROM t", 2).await;
+            for _ in 0..STREAM_WINDOW {
+                assert!(
+                    matches!(events.recv().await, Some(StreamEvent::Rows(rows)) if rows.len() == 2)
+                );
+            }
+            assert!(tokio::time::timeout(idle, events.recv()).await.is_err());
+
+            // The cursor is on the writer, which is free between chunks
+            tokio::time::timeout(
+                idle,
+                conn.execute("CREATE TABLE other (y)", QueryValues::default(), None),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+
+            conn.ack_stream(id).await;
+            assert!(
+                matches!(events.recv().await, Some(StreamEvent::Rows(rows)) if rows[0]["x"] == 5)
+            );
+            conn.cancel_stream(id).await;
+            assert!(matches!(events.recv().await, Some(StreamEvent::Error(_))));
+            assert!(events.recv().await.is_none());
+
+            let (id, mut events) = stream(&conn, "SELECT x FROM t", 4).await;
+            let mut total = 0;
+            while let Some(event) = events.recv().await {
+                match event {
+                    StreamEvent::Rows(rows) => {
+                        total += rows.len() as u64;
+                        conn.ack_stream(id).await;
+                    }
+                    StreamEvent::Done { total_rows } => {
+                        assert_eq!(total_rows, total);
+                        break;
+                    }
+                    StreamEvent::Error(e) => panic!("{e}"),
+                }
+            }
+            assert_eq!(total, 10);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn streams_on_the_writer_refuse_transactions_and_ddl() {
+        block_on(async {
+            let dir = test_dir("stream-writer");
+            let conn = open(&dir, 0).await;
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+            conn.execute(
+                "INSERT INTO t VALUES (1), (2), (3), (4)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+            let (id, mut events) = stream(&conn, "SELECT x FROM t", 1).await;
+            assert!(matches!(events.recv().await, Some(StreamEvent::Rows(_))));
+
+            // Between chunks: plain writes run, a transaction is refused
+            // instead of waiting on the stream, and DDL on `t` is locked
+            conn.execute("INSERT INTO t VALUES (5)", QueryValues::default(), None)
+                .await
+                .unwrap();
+            assert!(matches!(
+                conn.begin_transaction(DEFAULT_TRANSACTION_TIMEOUT).await,
+                Err(Error::OperationNotSupported(_))
+            ));
+            let dropped = conn.execute("DROP TABLE t", QueryValues::default(), None);
+            assert!(dropped.await.unwrap_err().to_string().contains("locked"));
+
+            // A batch that rolls back leaves the cursor be
+            let failing = BatchStatement {
+                query: "INSERT INTO missing VALUES (1)".into(),
+                values: QueryValues::default(),
+            };
+            assert!(conn.batch_params(vec![failing], None).await.is_err());
+            let mut total = 1;
+            while let Some(event) = events.recv().await {
+                match event {
+                    StreamEvent::Rows(rows) => total += rows.len(),
+                    StreamEvent::Done { .. } => break,
+                    StreamEvent::Error(e) => panic!("{e}"),
+                }
+                conn.ack_stream(id).await;
+            }
+            assert_eq!(total, 5);
+            while events.recv().await.is_some() {}
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            conn.rollback(id).await.unwrap();
+            conn.close().await;
+
+            // With a read pool, the stream doesn't get in the way
+            let conn = open(&dir, 1).await;
+            let (id, mut events) = stream(&conn, "SELECT x FROM t", 1).await;
+            assert!(matches!(events.recv().await, Some(StreamEvent::Rows(_))));
+            let tx = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            conn.execute("CREATE TABLE u (y)", QueryValues::default(), Some(tx))
+                .await
+                .unwrap();
+            conn.commit(tx).await.unwrap();
+            conn.cancel_stream(id).await;
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn stream_ends_when_the_consumer_goes_quiet() {
+        block_on(async {
+            let dir = test_dir("stream-timeout");
+            let mut conn = open(&dir, 1).await;
+            conn.stream_idle_timeout = Duration::from_millis(50);
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+            conn.execute(
+                "INSERT INTO t VALUES (1), (2), (3)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+            let (_, mut events) = stream(&conn, "SELECT x FROM t", 1).await;
+            for _ in 0..STREAM_WINDOW {
+                assert!(matches!(events.recv().await, Some(StreamEvent::Rows(_))));
+            }
+            assert!(
+                matches!(events.recv().await, Some(StreamEvent::Error(e)) if e.contains("timed out"))
+            );
+            assert!(events.recv().await.is_none());
+            assert!(conn.streams.lock().await.is_empty());
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn writes_through_select_and_backups_keep_readers_read_only() {
+        block_on(async {
+            let dir = test_dir("read-only-pool");
+            let conn = open(&dir, 2).await;
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            // Refused by the reader before it writes, then run once on the writer
+            let options = QueryOptions::default();
+            let inserted = conn
+                .select(
+                    "INSERT INTO t VALUES (1) RETURNING x",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap();
+            assert_eq!(json!(inserted), json!([{ "x": 1 }]));
+
+            conn.backup(&dir.join("backup.db"), None, |_| {})
+                .await
+                .unwrap();
+            for reader in &conn.readers {
+                let mut rows = reader
+                    .lock()
+                    .await
+                    .query("PRAGMA query_only", Params::None)
+                    .await
+                    .unwrap();
+                let row = rows.next().await.unwrap().unwrap();
+                assert_eq!(row.get::<i64>(0).unwrap(), 1);
+            }
+
+            let count = conn
+                .select(
+                    "SELECT count(*) AS n FROM t",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap();
+            assert_eq!(json!(count), json!([{ "n": 1 }]));
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn reads_see_the_writers_temp_tables_and_attached_databases() {
+        block_on(async {
+            let dir = test_dir("read-pool");
+            let conn = open(&dir, 2).await;
+            for sql in [
+                "CREATE TEMP TABLE scratch (x)",
+                "INSERT INTO scratch VALUES (1)",
+                "ATTACH DATABASE ':memory:' AS aux",
+                "CREATE TABLE aux.t (y)",
+                "INSERT INTO aux.t VALUES (2)",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+
+            let options = QueryOptions::default();
+            let select = |sql| conn.select(sql, QueryValues::default(), None, &options);
+            assert_eq!(
+                json!(select("SELECT x FROM scratch").await.unwrap()),
+                json!([{ "x": 1 }])
+            );
+            assert_eq!(
+                json!(select("SELECT y FROM aux.t").await.unwrap()),
+                json!([{ "y": 2 }])
+            );
+            assert!(select("SELECT * FROM missing").await.is_err());
+            let (_, mut events) = stream(&conn, "SELECT x FROM scratch", 10).await;
+            assert!(
+                matches!(events.recv().await, Some(StreamEvent::Rows(rows)) if rows.len() == 1)
+            );
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn query_reports_rows_and_the_statements_own_changes() {
+        block_on(async {
+            let dir = test_dir("query");
+            let conn = open(&dir, 0).await;
+            let options = QueryOptions::default();
+            let query = |sql| conn.query(sql, QueryValues::default(), None, &options);
+
+            for sql in [
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT)",
+                "CREATE TABLE log (item INTEGER)",
+                "CREATE TRIGGER logged AFTER INSERT ON items \
+                 BEGIN INSERT INTO log VALUES (new.id); INSERT INTO log VALUES (new.id); END",
+            ] {
+                query(sql).await.unwrap();
+            }
+
+            let inserted = query("INSERT INTO items (label) VALUES ('a'), ('b') RETURNING id")
+                .await
+                .unwrap();
+            assert_eq!(inserted.rows.columns, ["id"]);
+            assert_eq!(inserted.rows.rows, [[json!(1)], [json!(2)]]);
+            assert_eq!(inserted.rows_affected, 2);
+            assert_eq!(inserted.last_insert_id, 2);
+
+            let selected = query("SELECT count(*) AS n FROM log").await.unwrap();
+            assert_eq!(selected.rows.rows, [[json!(4)]]);
+            assert_eq!(selected.rows_affected, 0);
+
+            let updated = query("UPDATE items SET label = 'c'").await.unwrap();
+            assert!(updated.rows.columns.is_empty());
+            assert_eq!(updated.rows_affected, 2);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn restore_waits_for_its_database_without_holding_up_others() {
+        block_on(async {
+            let dir = test_dir("restore-gate");
+            let instances = Arc::new(DbInstances::default());
+            let other = DbConnection::connect(
+                "sqlite:other.db",
+                None,
+                dir.clone(),
+                None,
+                None,
+                0,
+                Encodings::default(),
+            )
+            .await
+            .unwrap();
+            {
+                let mut map = instances.0.lock().await;
+                map.insert("sqlite:test.db".into(), Arc::new(open(&dir, 0).await));
+                map.insert("sqlite:other.db".into(), Arc::new(other));
+            }
+            let conn = instances.get("sqlite:test.db").await.unwrap();
+            conn.backup(&dir.join("backup.db"), None, |_| {})
+                .await
+                .unwrap();
+
+            // An in-flight statement's handle keeps the restore waiting
+            let restore = tokio::spawn({
+                let instances = instances.clone();
+                let backup = dir.join("backup.db");
+                async move {
+                    instances
+                        .restore("sqlite:test.db", &backup, None, &[])
+                        .await
+                }
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(!restore.is_finished());
+
+            let wait = Duration::from_millis(50);
+            tokio::time::timeout(wait, instances.get("sqlite:other.db"))
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(tokio::time::timeout(wait, instances.get("sqlite:test.db"))
+                .await
+                .is_err());
+
+            drop(conn);
+            let restored = instances.get("sqlite:test.db").await.unwrap();
+            restore.await.unwrap().unwrap();
+            assert!(Arc::ptr_eq(
+                &restored,
+                &instances.get("sqlite:test.db").await.unwrap()
+            ));
+
+            for (_, conn) in instances.0.lock().await.drain() {
+                conn.close().await;
+            }
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    async fn count(conn: &DbConnection, transaction_id: Option<u64>) -> JsonValue {
+        let rows = conn
+            .select(
+                "SELECT count(*) AS n FROM t",
+                QueryValues::default(),
+                transaction_id,
+                &QueryOptions::default(),
+            )
+            .await
+            .unwrap();
+        json!(rows)[0]["n"].clone()
+    }
+
+    #[test]
+    fn transactions_commit_roll_back_and_expire() {
+        block_on(async {
+            let dir = test_dir("transactions");
+            let conn = open(&dir, 0).await;
+            let insert = |id| conn.execute("INSERT INTO t VALUES (1)", QueryValues::default(), id);
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            insert(Some(id)).await.unwrap();
+            conn.commit(id).await.unwrap();
+            assert_eq!(count(&conn, None).await, 1);
+            assert!(matches!(
+                conn.commit(id).await,
+                Err(Error::TransactionNotFound(_))
+            ));
+
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            insert(Some(id)).await.unwrap();
+            assert_eq!(count(&conn, Some(id)).await, 2);
+            conn.rollback(id).await.unwrap();
+            assert_eq!(count(&conn, None).await, 1);
+
+            let id = conn
+                .begin_transaction(Duration::from_millis(50))
+                .await
+                .unwrap();
+            insert(Some(id)).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert!(matches!(
+                insert(Some(id)).await,
+                Err(Error::TransactionNotFound(_))
+            ));
+            assert_eq!(count(&conn, None).await, 1);
+            assert!(conn.conn.lock().await.is_autocommit());
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn statements_outside_a_transaction_wait_for_it() {
+        block_on(async {
+            let dir = test_dir("transaction-queue");
+            let conn = Arc::new(open(&dir, 0).await);
+            conn.execute("CREATE TABLE t (x)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            let outside = tokio::spawn({
+                let conn = conn.clone();
+                async move {
+                    conn.execute("INSERT INTO t VALUES (2)", QueryValues::default(), None)
+                        .await
+                }
+            });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(!outside.is_finished());
+
+            conn.execute("INSERT INTO t VALUES (1)", QueryValues::default(), Some(id))
+                .await
+                .unwrap();
+            assert_eq!(count(&conn, Some(id)).await, 1);
+            conn.commit(id).await.unwrap();
+            outside.await.unwrap().unwrap();
+            assert_eq!(count(&conn, None).await, 2);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn failing_batches_undo_only_themselves() {
+        block_on(async {
+            let dir = test_dir("batch-params");
+            let conn = open(&dir, 0).await;
+            let statement = |query: &str| BatchStatement {
+                query: query.into(),
+                values: QueryValues::default(),
+            };
+            conn.execute("CREATE TABLE t (x UNIQUE)", QueryValues::default(), None)
+                .await
+                .unwrap();
+
+            let results = conn
+                .batch_params(
+                    vec![
+                        BatchStatement {
+                            query: "INSERT INTO t VALUES (?)".into(),
+                            values: QueryValues::Positional(vec![json!(1)]),
+                        },
+                        statement("SELECT x FROM t"),
+                    ],
+                    None,
+                )
+                .await
+                .unwrap();
+            assert!(matches!(&results[0], BatchResult::Execute(r) if r.rows_affected == 1));
+            assert_eq!(json!(results[1]), json!([{ "x": 1 }]));
+
+            // The duplicate fails and takes the insert before it along
+            let failing = || {
+                vec![
+                    statement("INSERT INTO t VALUES (2)"),
+                    statement("INSERT INTO t VALUES (1)"),
+                ]
+            };
+            assert!(conn.batch_params(failing(), None).await.is_err());
+            assert_eq!(count(&conn, None).await, 1);
+            assert!(conn.conn.lock().await.is_autocommit());
+
+            // Inside a transaction, the transaction's own writes survive
+            let id = conn
+                .begin_transaction(DEFAULT_TRANSACTION_TIMEOUT)
+                .await
+                .unwrap();
+            conn.execute("INSERT INTO t VALUES (3)", QueryValues::default(), Some(id))
+                .await
+                .unwrap();
+            assert!(conn.batch_params(failing(), Some(id)).await.is_err());
+            assert_eq!(count(&conn, Some(id)).await, 2);
+            conn.commit(id).await.unwrap();
+            assert_eq!(count(&conn, None).await, 2);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn columnar_results_keep_duplicate_column_names() {
+        block_on(async {
+            let dir = test_dir("columnar");
+            let conn = open(&dir, 0).await;
+            for sql in [
+                "CREATE TABLE a (id INTEGER PRIMARY KEY)",
+                "CREATE TABLE b (id INTEGER PRIMARY KEY, a_id INTEGER)",
+                "INSERT INTO a VALUES (1)",
+                "INSERT INTO b VALUES (2, 1)",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+
+            let options = QueryOptions {
+                format: RowFormat::Columnar,
+                ..QueryOptions::default()
+            };
+            let result = conn
+                .select(
+                    "SELECT a.id, b.id FROM a JOIN b ON b.a_id = a.id",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap();
+            let SelectResult::Columnar(result) = result else {
+                panic!("expected a columnar result");
+            };
+            assert_eq!(result.columns, ["id", "id"]);
+            assert_eq!(result.rows, [[json!(1), json!(2)]]);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn columnar_metadata_traces_aliases_to_their_origin() {
+        block_on(async {
+            let dir = test_dir("metadata");
+            let conn = open(&dir, 0).await;
+            for sql in [
+                "CREATE TABLE items (id INTEGER PRIMARY KEY, label TEXT)",
+                "CREATE TABLE events (item_id INTEGER, at DATETIME)",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+
+            let options = QueryOptions {
+                format: RowFormat::Columnar,
+                ..QueryOptions::default()
+            };
+            let result = conn
+                .select(
+                    "SELECT i.label AS name, e.at, count(*) AS n \
+                     FROM items i JOIN events e ON e.item_id = i.id GROUP BY i.id",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap();
+            let SelectResult::Columnar(result) = result else {
+                panic!("expected a columnar result");
+            };
+            let origin = |name, decl_type, table, column| {
+                json!({
+                    "name": name,
+                    "declType": decl_type,
+                    "table": table,
+                    "column": column,
+                    "database": "main",
+                })
+            };
+            assert_eq!(
+                json!(result.metadata),
+                json!([
+                    origin("name", "TEXT", "items", "label"),
+                    origin("at", "DATETIME", "events", "at"),
+                    {
+                        "name": "n",
+                        "declType": null,
+                        "table": null,
+                        "column": null,
+                        "database": null,
+                    },
+                ])
+            );
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn typed_converts_only_boolean_columns() {
+        block_on(async {
+            let dir = test_dir("typed");
+            let conn = open(&dir, 0).await;
+            for sql in [
+                "CREATE TABLE flags (done BOOLEAN, n INTEGER, at DATETIME, day DATE)",
+                "INSERT INTO flags VALUES (1, 1, 1700000000, '2024-01-02')",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+
+            let options = QueryOptions {
+                typed: true,
+                ..QueryOptions::default()
+            };
+            let result = conn
+                .select(
+                    "SELECT * FROM flags",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                json!(result),
+                json!([{ "done": true, "n": 1, "at": 1700000000, "day": "2024-01-02" }])
+            );
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn scripts_count_rows_like_execute() {
+        block_on(async {
+            let dir = test_dir("script-changes");
+            let conn = open(&dir, 0).await;
+
+            let result = conn
+                .execute_script(
+                    "CREATE TABLE items (id INTEGER PRIMARY KEY);
+                     CREATE TABLE log (id INTEGER);
+                     CREATE TRIGGER logged AFTER INSERT ON items BEGIN
+                         INSERT INTO log VALUES (new.id);
+                         INSERT INTO log VALUES (new.id);
+                     END;
+                     INSERT INTO items VALUES (1), (2);
+                     CREATE INDEX log_id ON log (id);
+                     SELECT count(*) FROM log;
+                     UPDATE items SET id = id + 10 WHERE id = 1;",
+                    None,
+                    false,
+                )
+                .await
+                .unwrap();
+            // 2 inserted + 1 updated; the trigger's 4 log rows and the
+            // statements that change nothing don't count
+            assert_eq!(result.statements, 7);
+            assert_eq!(result.rows_affected, 3);
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn base64_blobs_bind_back_as_blobs() {
+        block_on(async {
+            let dir = test_dir("base64-round-trip");
+            let conn = open(&dir, 0).await;
+            for sql in [
+                "CREATE TABLE photos (data)",
+                "INSERT INTO photos VALUES (x'00ff10')",
+            ] {
+                conn.execute(sql, QueryValues::default(), None)
+                    .await
+                    .unwrap();
+            }
+
+            let options = QueryOptions {
+                blob_encoding: Some(BlobEncoding::Base64),
+                ..QueryOptions::default()
+            };
+            let SelectResult::Objects(rows) = conn
+                .select(
+                    "SELECT data FROM photos",
+                    QueryValues::default(),
+                    None,
+                    &options,
+                )
+                .await
+                .unwrap()
+            else {
+                panic!("expected object rows");
+            };
+            let data = rows[0]["data"].clone();
+            assert_eq!(data, json!({ "$type": "blob", "base64": "AP8Q" }));
+
+            conn.execute(
+                "INSERT INTO photos VALUES (?)",
+                QueryValues::Positional(vec![data]),
+                None,
+            )
+            .await
+            .unwrap();
+            let result = conn
+                .select(
+                    "SELECT typeof(data) AS type, hex(data) AS hex FROM photos",
+                    QueryValues::default(),
+                    None,
+                    &QueryOptions::default(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                json!(result),
+                json!([
+                    { "type": "blob", "hex": "00FF10" },
+                    { "type": "blob", "hex": "00FF10" },
+                ])
+            );
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
+    }
+
+    #[test]
+    fn int64_columns_encode_every_integer() {
+        block_on(async {
+            let dir = test_dir("int64-columns");
+            let conn = open(&dir, 0).await;
+            conn.execute(
+                "CREATE TABLE t (id INTEGER, n INTEGER)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+            conn.execute(
+                "INSERT INTO t VALUES (1, 1), (9007199254740993, 9007199254740993)",
+                QueryValues::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+            let select = |integer_encoding| {
+                let options = QueryOptions {
+                    integer_encoding: Some(integer_encoding),
+                    int64_columns: vec!["id".into()],
+                    ..QueryOptions::default()
+                };
+                let conn = &conn;
+                async move {
+                    let sql = "SELECT id, n FROM t ORDER BY id";
+                    let rows = conn.select(sql, QueryValues::default(), None, &options);
+                    json!(rows.await.unwrap())
+                }
+            };
+
+            // Listed columns are always encoded; others only past 2^53
+            assert_eq!(
+                select(IntegerEncoding::String).await,
+                json!([
+                    { "id": "1", "n": 1 },
+                    { "id": "9007199254740993", "n": "9007199254740993" },
+                ])
+            );
+            let int64 = |value: &str| json!({ "$type": "int64", "value": value });
+            assert_eq!(
+                select(IntegerEncoding::BigInt).await,
+                json!([
+                    { "id": int64("1"), "n": 1 },
+                    { "id": int64("9007199254740993"), "n": int64("9007199254740993") },
+                ])
+            );
+
+            conn.close().await;
+            let _ = std::fs::remove_dir_all(&dir);
+        });
     }
 }
@@ -7,7 +7,7 @@ interface HarnessState {
 
 interface InvokeArgs {
   query?: string;
-  queries?: string[];
+  script?: string;
 }
 
 interface Harness {
@@ -95,19 +95,23 @@ function createHarness({
         return state.hashes.map((hash) => ({ hash }));
       }
 
-      if (command === "plugin:libsql|batch") {
+      if (command === "plugin:libsql|execute_script") {
         const next: HarnessState = {
           hashes: [...state.hashes],
           userColumns: [...state.userColumns],
         };
 
-        for (const query of args.queries ?? []) {
+        // The fixtures have no `;` inside statements, so a plain split will do
+        const statements = (args.script ?? "")
+          .split(";")
+          .filter((statement) => statement.trim() !== "");
+        for (const query of statements) {
           applyQuery(next, query);
         }
 
         state.hashes = next.hashes;
         state.userColumns = next.userColumns;
-        return undefined;
+        return { statements: statements.length, rowsAffected: 0 };
       }
 
       throw new Error(`Unexpected command: ${command}`);
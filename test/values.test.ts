@@ -0,0 +1,41 @@
+import { expect, mock, test } from "bun:test";
+
+interface InvokeArgs {
+  query?: string;
+  values?: unknown;
+}
+
+const calls: { command: string; args: InvokeArgs }[] = [];
+
+mock.module("@tauri-apps/api/core", () => ({
+  invoke(command: string, args: InvokeArgs) {
+    calls.push({ command, args });
+
+    if (command === "plugin:libsql|select") {
+      // What the plugin returns for `x'00ff10'` with `blobEncoding: "base64"`
+      return Promise.resolve([{ data: { $type: "blob", base64: "AP8Q" } }]);
+    }
+    if (command === "plugin:libsql|execute") {
+      return Promise.resolve({ rowsAffected: 1, lastInsertId: 2 });
+    }
+
+    throw new Error(`Unexpected command: ${command}`);
+  },
+}));
+
+test("binds base64 blobs from a select back as blobs", async () => {
+  const { Database } = await import("../dist-js/index.js");
+  const db = new Database("sqlite:photos.db");
+
+  const [{ data }] = await db.select<{ data: Uint8Array }[]>(
+    "SELECT data FROM photos",
+    [],
+    { blobEncoding: "base64" },
+  );
+  expect(data).toEqual(new Uint8Array([0, 255, 16]));
+
+  await db.execute("INSERT INTO photos (data) VALUES ($1)", [data]);
+  expect(calls.at(-1)?.args.values).toEqual([
+    { $type: "blob", base64: "AP8Q" },
+  ]);
+});